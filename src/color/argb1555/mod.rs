@@ -0,0 +1,99 @@
+use std::fmt;
+
+use super::{expand_to_8_bits, reduce_to_bits, Color, Pixel};
+
+#[cfg(test)]
+mod tests;
+
+/// A 16 bit color with an alpha bit above 5 bits each of red, green and blue.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Argb1555 {
+    data: u16,
+}
+
+impl Argb1555 {
+    fn a1(self) -> u16 {
+        self.data >> 15
+    }
+    fn r5(self) -> u16 {
+        (self.data >> 10) & 0x1F
+    }
+    fn g5(self) -> u16 {
+        (self.data >> 5) & 0x1F
+    }
+    fn b5(self) -> u16 {
+        self.data & 0x1F
+    }
+}
+
+impl Color for Argb1555 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Argb1555 {
+        let (r_float, g_float, b_float, a_float) = components;
+
+        if a_float < 0.5 {
+            return Argb1555 { data: 0 };
+        }
+
+        let r = reduce_to_bits(r_float, 5);
+        let g = reduce_to_bits(g_float, 5);
+        let b = reduce_to_bits(b_float, 5);
+        Argb1555 {
+            data: (1 << 15) | (r << 10) | (g << 5) | b,
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        if self.a1() == 0 {
+            return Pixel { data: [0, 0, 0, 0] };
+        }
+
+        let r = expand_to_8_bits(self.r5(), 5);
+        let g = expand_to_8_bits(self.g5(), 5);
+        let b = expand_to_8_bits(self.b5(), 5);
+        Pixel {
+            data: [r, g, b, 0xFF],
+        }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        if self.a1() == 0 {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+
+        let r = f64::from(self.r5()) / 31.0;
+        let g = f64::from(self.g5()) / 31.0;
+        let b = f64::from(self.b5()) / 31.0;
+        (r, g, b, 1.0)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_le_bytes().to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Argb1555> {
+        Some(Argb1555 {
+            data: u16::from_le_bytes([bytes[0], bytes[1]]),
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+
+    fn native_is_little_endian(_: ()) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Argb1555 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Argb1555")
+            .field("r", &self.r5())
+            .field("g", &self.g5())
+            .field("b", &self.b5())
+            .field("a", &self.a1())
+            .finish()
+    }
+}
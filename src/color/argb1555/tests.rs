@@ -0,0 +1,75 @@
+use super::super::*;
+
+use k_means::SimpleInput;
+
+#[test]
+fn rgba8_to_argb1555_test() {
+    let test_data = [
+        (
+            [0xFF, 0x00, 0x08, 0xFF],
+            (1 << 15) | (0x1F << 10) | (0 << 5) | 1,
+        ),
+        (
+            [0xED, 0x04, 0x05, 0x80],
+            (1 << 15) | (0x1D << 10) | (0 << 5) | 1,
+        ),
+        ([0xED, 0x04, 0x05, 0x7F], 0),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Argb1555>::from(Pixel { data: test_data });
+        let expected = Argb1555 {
+            data: expected_data,
+        };
+        let result = test_color.as_output();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn argb1555_as_pixel_test() {
+    let test_data = [
+        (
+            (1 << 15) | (0x1F << 10) | (0 << 5) | 1,
+            [0xFF, 0x00, 0x08, 0xFF],
+        ),
+        (
+            (1 << 15) | (0x1D << 10) | (0 << 5) | 1,
+            [0xEF, 0x00, 0x08, 0xFF],
+        ),
+        ((0x1D << 10) | (0 << 5) | 1, [0x00, 0x00, 0x00, 0x00]),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = Argb1555 { data: test_data };
+        let expected = Pixel {
+            data: expected_data,
+        };
+        let result = test_color.as_pixel();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn argb1555_round_trips_through_pixel() {
+    for data in 0x8000..=0xFFFFu32 {
+        let color = Argb1555 { data: data as u16 };
+        let round_tripped = Argb1555::new(Rgba8::from(color.as_pixel()).components(), ());
+        assert_eq!(color, round_tripped);
+    }
+}
+
+#[test]
+fn argb1555_rgb_is_zero_if_alpha_is() {
+    for alpha in 0..=0xFF {
+        for &rgb in &[[0xFF, 0xFF, 0xFF], [0x40, 0x80, 0xC0]] {
+            let pixel = Pixel {
+                data: [rgb[0], rgb[1], rgb[2], alpha],
+            };
+            let result = ConvertibleColor::<Rgba8, Argb1555>::from(pixel)
+                .as_output()
+                .as_pixel();
+            if result.data[3] == 0 {
+                assert_eq!(result.data, [0, 0, 0, 0]);
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! 15 bit colors as used by the SNES, GBA and DS, with red in the low bits.
+//!
+//! These consoles have no per-color alpha in their palettes; instead color
+//! index 0 is drawn as transparent, so it's reserved when quantizing to them.
+
+use std::fmt;
+
+use super::{reduce_to_bits, Color, Pixel};
+
+#[cfg(test)]
+mod tests;
+
+/// Expands a 5 bit channel to 8 bits the way the consoles' video output
+/// does, repeating the high bits in the low ones.
+fn expand_5_bits(value: u16) -> u8 {
+    ((value << 3) | (value >> 2)) as u8
+}
+
+fn bgr_from_components(r: f64, g: f64, b: f64) -> u16 {
+    let r = reduce_to_bits(r, 5);
+    let g = reduce_to_bits(g, 5);
+    let b = reduce_to_bits(b, 5);
+    (b << 10) | (g << 5) | r
+}
+
+/// A SNES or GBA color, with 5 bits each of red, green and blue.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Bgr555 {
+    data: u16,
+}
+
+impl Bgr555 {
+    fn r5(self) -> u16 {
+        self.data & 0x1F
+    }
+    fn g5(self) -> u16 {
+        (self.data >> 5) & 0x1F
+    }
+    fn b5(self) -> u16 {
+        (self.data >> 10) & 0x1F
+    }
+}
+
+impl Color for Bgr555 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Bgr555 {
+        let (r_float, g_float, b_float, _a_float) = components;
+        Bgr555 {
+            data: bgr_from_components(r_float, g_float, b_float),
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let r = expand_5_bits(self.r5());
+        let g = expand_5_bits(self.g5());
+        let b = expand_5_bits(self.b5());
+        Pixel {
+            data: [r, g, b, 0xFF],
+        }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let r = f64::from(self.r5()) / 31.0;
+        let g = f64::from(self.g5()) / 31.0;
+        let b = f64::from(self.b5()) / 31.0;
+        (r, g, b, 1.0)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_le_bytes().to_vec()
+    }
+
+    /// The top bit is unused, and ignored.
+    fn from_native(bytes: &[u8], _: ()) -> Option<Bgr555> {
+        Some(Bgr555 {
+            data: u16::from_le_bytes([bytes[0], bytes[1]]) & 0x7FFF,
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+
+    fn native_is_little_endian(_: ()) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Bgr555 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Bgr555")
+            .field("r", &self.r5())
+            .field("g", &self.g5())
+            .field("b", &self.b5())
+            .finish()
+    }
+}
+
+/// A DS color, like `Bgr555` with an alpha flag in the top bit.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Abgr1555 {
+    data: u16,
+}
+
+impl Abgr1555 {
+    fn a1(self) -> u16 {
+        self.data >> 15
+    }
+    fn r5(self) -> u16 {
+        self.data & 0x1F
+    }
+    fn g5(self) -> u16 {
+        (self.data >> 5) & 0x1F
+    }
+    fn b5(self) -> u16 {
+        (self.data >> 10) & 0x1F
+    }
+}
+
+impl Color for Abgr1555 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Abgr1555 {
+        let (r_float, g_float, b_float, a_float) = components;
+        let data = if a_float < 0.5 {
+            0
+        } else {
+            (1 << 15) | bgr_from_components(r_float, g_float, b_float)
+        };
+        Abgr1555 { data }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let r = expand_5_bits(self.r5());
+        let g = expand_5_bits(self.g5());
+        let b = expand_5_bits(self.b5());
+        let a = if self.a1() == 1 { 0xFF } else { 0 };
+        Pixel { data: [r, g, b, a] }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let r = f64::from(self.r5()) / 31.0;
+        let g = f64::from(self.g5()) / 31.0;
+        let b = f64::from(self.b5()) / 31.0;
+        (r, g, b, f64::from(self.a1()))
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_le_bytes().to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Abgr1555> {
+        Some(Abgr1555 {
+            data: u16::from_le_bytes([bytes[0], bytes[1]]),
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+
+    fn native_is_little_endian(_: ()) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Abgr1555 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Abgr1555")
+            .field("r", &self.r5())
+            .field("g", &self.g5())
+            .field("b", &self.b5())
+            .field("a", &self.a1())
+            .finish()
+    }
+}
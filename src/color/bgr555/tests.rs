@@ -0,0 +1,91 @@
+use super::super::*;
+use super::expand_5_bits;
+
+use k_means::SimpleInput;
+
+#[test]
+fn rgba8_to_bgr555_test() {
+    let test_data = [
+        ([0xFF, 0x00, 0x08, 0xFF], (1 << 10) | (0 << 5) | 0x1F),
+        ([0xED, 0x04, 0x05, 0xFF], (1 << 10) | (0 << 5) | 0x1D),
+        ([0x80, 0x80, 0x80, 0xFF], (0x10 << 10) | (0x10 << 5) | 0x10),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Bgr555>::from(Pixel { data: test_data });
+        let expected = Bgr555 {
+            data: expected_data,
+        };
+        let result = test_color.as_output();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn rgba8_to_abgr1555_test() {
+    let test_data = [
+        ([0xFF, 0x00, 0x08, 0xFF], (1 << 15) | (1 << 10) | 0x1F),
+        ([0xFF, 0x00, 0x08, 0x80], (1 << 15) | (1 << 10) | 0x1F),
+        ([0xFF, 0x00, 0x08, 0x7F], 0),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Abgr1555>::from(Pixel { data: test_data });
+        let expected = Abgr1555 {
+            data: expected_data,
+        };
+        let result = test_color.as_output();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn bgr555_as_pixel_test() {
+    let test_data = [
+        ((1 << 10) | (0 << 5) | 0x1F, [0xFF, 0x00, 0x08, 0xFF]),
+        ((1 << 10) | (0x0F << 5) | 0x1D, [0xEF, 0x7B, 0x08, 0xFF]),
+        ((0x10 << 10) | (0x10 << 5) | 0x10, [0x84, 0x84, 0x84, 0xFF]),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = Bgr555 { data: test_data };
+        let expected = Pixel {
+            data: expected_data,
+        };
+        let result = test_color.as_pixel();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn bgr555_round_trips_through_pixel() {
+    for data in 0..0x8000u16 {
+        let color = Bgr555 { data };
+        let round_tripped = Bgr555::new(Rgba8::from(color.as_pixel()).components(), ());
+        assert_eq!(color, round_tripped);
+
+        let color = Abgr1555 {
+            data: data | (1 << 15),
+        };
+        let round_tripped = Abgr1555::new(Rgba8::from(color.as_pixel()).components(), ());
+        assert_eq!(color, round_tripped);
+    }
+}
+
+#[test]
+fn bgr555_native_is_little_endian() {
+    let color = Bgr555 {
+        data: (0x12 << 10) | (0x0A << 5) | 0x05,
+    };
+    assert_eq!(vec![0x45, 0x49], color.to_native());
+
+    let color = Abgr1555 {
+        data: (1 << 15) | (0x12 << 10) | (0x0A << 5) | 0x05,
+    };
+    assert_eq!(vec![0x45, 0xC9], color.to_native());
+}
+
+#[test]
+fn expand_5_bits_test() {
+    let test_data = [(0, 0), (1, 0x08), (0x0F, 0x7B), (0x10, 0x84), (0x1F, 0xFF)];
+    for &(value, expected_result) in &test_data {
+        assert_eq!(expected_result, expand_5_bits(value));
+    }
+}
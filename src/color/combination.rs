@@ -2,43 +2,102 @@
 //!
 //! Each color combination represents the list of colors that appear in a
 //! given pixel location, with one color per input image.
+//!
+//! The distance between combinations is the sum of the distances between
+//! their colors, scaled by each image's weight when weights are set, with
+//! transparency compared as the alpha settings say.
+
+use std::hash::{Hash, Hasher};
 
-use color::{Color, ConvertibleColor, Pixel};
+use num::{Float, FromPrimitive, NumCast, Zero};
+
+use color::{AlphaDistance, Color, ConvertibleColor, Pixel};
 use k_means::{Grouped, Input, Output, SimpleInput};
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub struct ColorCombination<T: Color> {
+/// How the distance between combinations is measured. Every combination
+/// refers to the metric it was created with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metric {
+    /// How much each image counts toward the distance, or None for every
+    /// image to count the same.
+    pub image_weights: Option<Vec<f64>>,
+    pub alpha: AlphaDistance,
+}
+
+/// Sums the distances between each image's colors, scaling them by the
+/// image's weight if there are weights.
+pub fn weighted_sum<D, I>(distances: I, weights: Option<&[f64]>) -> D
+where
+    D: Float,
+    I: Iterator<Item = D>,
+{
+    match weights {
+        Some(weights) => distances
+            .zip(weights.iter())
+            .map(|(distance, &weight)| distance * <D as NumCast>::from(weight).unwrap())
+            .fold(D::zero(), |sum, distance| sum + distance),
+        None => distances.fold(D::zero(), |sum, distance| sum + distance),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorCombination<'m, T: Color> {
     colors: Vec<T>,
+    metric: &'m Metric,
 }
 
-impl<T: Color> ColorCombination<T> {
-    pub fn new(colors: Vec<T>) -> ColorCombination<T> {
-        ColorCombination { colors }
+impl<'m, T: Color> ColorCombination<'m, T> {
+    pub fn new(colors: Vec<T>, metric: &'m Metric) -> ColorCombination<'m, T> {
+        ColorCombination { colors, metric }
+    }
+    pub fn metric(&self) -> &'m Metric {
+        self.metric
     }
     pub fn as_pixels(&self) -> Vec<Pixel> {
         self.colors.iter().map(Color::as_pixel).collect()
     }
 }
 
-impl<T: Color> Output for ColorCombination<T> {
+// Only combinations with the same metric are ever compared, so the colors
+// are enough.
+impl<'m, T: Color> PartialEq for ColorCombination<'m, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.colors == other.colors
+    }
+}
+
+impl<'m, T: Color> Eq for ColorCombination<'m, T> {}
+
+impl<'m, T: Color> Hash for ColorCombination<'m, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.colors.hash(state);
+    }
+}
+
+impl<'m, T: Color> Output for ColorCombination<'m, T> {
     type Distance = T::Distance;
-    fn distance_to(&self, other: &ColorCombination<T>) -> Self::Distance {
-        self.colors
+    fn distance_to(&self, other: &ColorCombination<'m, T>) -> Self::Distance {
+        let distances = self
+            .colors
             .iter()
             .zip(other.colors.iter())
-            .map(|(c1, c2)| c1.distance_to(c2))
-            .sum()
+            .map(|(c1, c2)| T::Distance::from_f64(self.metric.alpha.between(c1, c2)).unwrap());
+        weighted_sum(distances, self.metric.image_weights.as_deref())
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub struct ConvertibleColorCombination<I: Color, O: Color> {
+#[derive(Clone, Debug)]
+pub struct ConvertibleColorCombination<'m, I: Color, O: Color> {
     colors: Vec<ConvertibleColor<I, O>>,
+    metric: &'m Metric,
 }
 
-impl<I: Color, O: Color> ConvertibleColorCombination<I, O> {
-    pub fn new(colors: Vec<ConvertibleColor<I, O>>) -> ConvertibleColorCombination<I, O> {
-        ConvertibleColorCombination { colors }
+impl<'m, I: Color, O: Color> ConvertibleColorCombination<'m, I, O> {
+    pub fn new(
+        colors: Vec<ConvertibleColor<I, O>>,
+        metric: &'m Metric,
+    ) -> ConvertibleColorCombination<'m, I, O> {
+        ConvertibleColorCombination { colors, metric }
     }
     pub fn as_pixels(&self) -> Vec<Pixel> {
         self.colors
@@ -46,50 +105,80 @@ impl<I: Color, O: Color> ConvertibleColorCombination<I, O> {
             .map(|input_color| input_color.color.as_pixel())
             .collect()
     }
+    /// The largest distance from one of the colors to its counterpart.
+    pub fn max_distance_to(&self, other: &ColorCombination<'m, O>) -> I::Distance {
+        self.colors
+            .iter()
+            .zip(other.colors.iter())
+            .map(|(c1, c2)| c1.distance_with(c2, self.metric.alpha))
+            .fold(I::Distance::zero(), Float::max)
+    }
 }
 
-impl<I: Color, O: Color> SimpleInput for ConvertibleColorCombination<I, O> {
-    type Output = ColorCombination<O>;
+impl<'m, I: Color, O: Color> PartialEq for ConvertibleColorCombination<'m, I, O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.colors == other.colors
+    }
+}
+
+impl<'m, I: Color, O: Color> Eq for ConvertibleColorCombination<'m, I, O> {}
+
+impl<'m, I: Color, O: Color> Hash for ConvertibleColorCombination<'m, I, O> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.colors.hash(state);
+    }
+}
+
+impl<'m, I: Color, O: Color> SimpleInput for ConvertibleColorCombination<'m, I, O> {
+    type Output = ColorCombination<'m, O>;
     type Distance = I::Distance;
 
     fn distance_to(&self, other: &Self::Output) -> Self::Distance {
-        self.colors
+        let distances = self
+            .colors
             .iter()
             .zip(other.colors.iter())
-            .map(|(c1, c2)| c1.distance_to(c2))
-            .sum()
+            .map(|(c1, c2)| c1.distance_with(c2, self.metric.alpha));
+        weighted_sum(distances, self.metric.image_weights.as_deref())
     }
 
     fn normalized_distance(&self, other: &Self::Output) -> Self::Distance {
-        self.colors
+        let distances = self
+            .colors
             .iter()
             .zip(other.colors.iter())
-            .map(|(c1, c2)| c1.normalized_distance(c2))
-            .sum()
+            .map(|(c1, c2)| c1.normalized_distance_with(c2, self.metric.alpha));
+        weighted_sum(distances, self.metric.image_weights.as_deref())
     }
 
     fn as_output(&self) -> Self::Output {
-        ColorCombination::new(self.colors.iter().map(SimpleInput::as_output).collect())
+        ColorCombination::new(
+            self.colors.iter().map(SimpleInput::as_output).collect(),
+            self.metric,
+        )
     }
 }
 
-impl<I: Color, O: Color> Input for Grouped<ConvertibleColorCombination<I, O>> {
-    fn mean_of(grouped_colorsets: &[&Grouped<ConvertibleColorCombination<I, O>>]) -> Self::Output {
+impl<'m, I: Color, O: Color> Input for Grouped<ConvertibleColorCombination<'m, I, O>> {
+    fn mean_of(
+        grouped_colorsets: &[&Grouped<ConvertibleColorCombination<'m, I, O>>],
+    ) -> Self::Output {
         mean_of(grouped_colorsets)
     }
 }
 
-fn mean_of<I: Color, O: Color>(
-    grouped_colorsets: &[&Grouped<ConvertibleColorCombination<I, O>>],
-) -> ColorCombination<O> {
+fn mean_of<'m, I: Color, O: Color>(
+    grouped_colorsets: &[&Grouped<ConvertibleColorCombination<'m, I, O>>],
+) -> ColorCombination<'m, O> {
     let color_count = grouped_colorsets[0].data.colors.len();
     let mean_colors = (0..color_count)
         .map(|i| {
             let color_iter = grouped_colorsets
                 .iter()
                 .map(|&group| (&group.data.colors[i], group.count));
-            ::color::mean_of_colors(color_iter)
+            let context = grouped_colorsets[0].data.colors[i].output_context;
+            ::color::mean_of_colors(color_iter, context)
         })
         .collect();
-    ColorCombination::new(mean_colors)
+    ColorCombination::new(mean_colors, grouped_colorsets[0].data.metric)
 }
@@ -0,0 +1,384 @@
+//! Colors in a format described at runtime by its bit layout, for hardware
+//! without a built-in color type.
+//!
+//! A layout lists channels from the highest bits to the lowest, each a letter
+//! followed by its number of bits: R, G and B for color, I for intensity in
+//! their place, A for alpha, and X for unused padding. `R5G6B5` is the same
+//! grid as RGB565, and `I4A4` as IA4.
+//!
+//! Two layouts of the same size can be joined with `|`, as in
+//! `A3R4G4B4|R5G5B5`, for a format with two modes like RGB5A3. A flag bit
+//! above them selects the mode: clear for the first, set for the second. Each
+//! color is stored in whichever mode represents it most closely.
+//!
+//! Intensity is taken from color with the luma coefficients of the type
+//! parameter, as for IA8 and IA4.
+//!
+//! Native values are big-endian unless the layout ends with `:le`.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use super::{expand_to_8_bits, reduce_to_bits, Color, LumaCoefficients, Pixel, Rgba8};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ChannelKind {
+    Red,
+    Green,
+    Blue,
+    Intensity,
+    Alpha,
+    Padding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Channel {
+    kind: ChannelKind,
+    bits: u32,
+    shift: u32,
+}
+
+impl Channel {
+    fn max(self) -> u32 {
+        (1 << self.bits) - 1
+    }
+
+    fn get(self, data: u32) -> u16 {
+        ((data >> self.shift) & self.max()) as u16
+    }
+}
+
+/// One layout of a format's channels.
+#[derive(Clone, Debug, PartialEq)]
+struct Mode {
+    channels: Vec<Channel>,
+}
+
+impl Mode {
+    fn channel(&self, kind: ChannelKind) -> Option<Channel> {
+        self.channels
+            .iter()
+            .cloned()
+            .find(|channel| channel.kind == kind)
+    }
+
+    fn encode<L: LumaCoefficients>(&self, components: (f64, f64, f64, f64)) -> u32 {
+        let (r, g, b, a) = components;
+
+        if let Some(alpha) = self.channel(ChannelKind::Alpha) {
+            if reduce_to_bits(a, alpha.bits) == 0 {
+                return 0;
+            }
+        }
+
+        let mut data = 0;
+        for channel in &self.channels {
+            let component = match channel.kind {
+                ChannelKind::Red => r,
+                ChannelKind::Green => g,
+                ChannelKind::Blue => b,
+                ChannelKind::Intensity => L::intensity(r, g, b),
+                ChannelKind::Alpha => a,
+                ChannelKind::Padding => continue,
+            };
+            data |= u32::from(reduce_to_bits(component, channel.bits)) << channel.shift;
+        }
+        data
+    }
+
+    /// The value of each channel, scaled by `scale` from its number of bits.
+    fn decode<T, F>(&self, data: u32, scale: F) -> (T, T, T, Option<T>)
+    where
+        T: Copy,
+        F: Fn(u16, u32) -> T,
+    {
+        let value = |kind| {
+            self.channel(kind)
+                .map(|channel| scale(channel.get(data), channel.bits))
+        };
+        let a = value(ChannelKind::Alpha);
+        match value(ChannelKind::Intensity) {
+            Some(i) => (i, i, i, a),
+            None => (
+                value(ChannelKind::Red).unwrap(),
+                value(ChannelKind::Green).unwrap(),
+                value(ChannelKind::Blue).unwrap(),
+                a,
+            ),
+        }
+    }
+}
+
+/// A color format parsed from a bit layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    layout: String,
+    modes: Vec<Mode>,
+    /// The number of bits in a color, including the mode flag.
+    bits: u32,
+    little_endian: bool,
+}
+
+impl Format {
+    pub fn parse(layout: &str) -> Result<Format, String> {
+        let invalid = |reason: &str| format!("Invalid color format {}: {}", layout, reason);
+
+        let (modes_layout, little_endian) = match layout.rfind(':') {
+            Some(colon) => match layout[colon + 1..].to_lowercase().as_str() {
+                "le" => (&layout[..colon], true),
+                "be" => (&layout[..colon], false),
+                _ => return Err(invalid("the byte order must be :le or :be")),
+            },
+            None => (layout, false),
+        };
+
+        let modes = modes_layout
+            .split('|')
+            .map(|mode_layout| parse_mode(mode_layout).map_err(|reason| invalid(&reason)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if modes.len() > 2 {
+            return Err(invalid("there can be at most two modes"));
+        }
+
+        let mode_bits = modes[0]
+            .channels
+            .iter()
+            .map(|channel| channel.bits)
+            .sum::<u32>();
+        if modes.iter().any(|mode| {
+            mode.channels
+                .iter()
+                .map(|channel| channel.bits)
+                .sum::<u32>()
+                != mode_bits
+        }) {
+            return Err(invalid("both modes must have the same number of bits"));
+        }
+
+        let bits = mode_bits + modes.len() as u32 - 1;
+        if bits > 32 {
+            return Err(invalid("colors can be at most 32 bits"));
+        }
+
+        Ok(Format {
+            layout: layout.to_string(),
+            modes,
+            bits,
+            little_endian,
+        })
+    }
+
+    /// The layout the format was parsed from.
+    pub fn layout(&self) -> &str {
+        &self.layout
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.modes
+            .iter()
+            .any(|mode| mode.channel(ChannelKind::Alpha).is_some())
+    }
+
+    pub fn is_grayscale(&self) -> bool {
+        self.modes
+            .iter()
+            .all(|mode| mode.channel(ChannelKind::Intensity).is_some())
+    }
+
+    fn native_size(&self) -> usize {
+        ((self.bits + 7) / 8) as usize
+    }
+
+    fn flag_shift(&self) -> u32 {
+        self.bits - 1
+    }
+
+    fn mode_of(&self, data: u32) -> &Mode {
+        if self.modes.len() == 2 && (data >> self.flag_shift()) & 1 == 1 {
+            &self.modes[1]
+        } else {
+            &self.modes[0]
+        }
+    }
+}
+
+/// Parses a single mode's channels, such as `A3R4G4B4`.
+fn parse_mode(layout: &str) -> Result<Mode, String> {
+    let mut channels: Vec<Channel> = Vec::new();
+    let mut characters = layout.chars().peekable();
+    while let Some(letter) = characters.next() {
+        let kind = match letter.to_ascii_uppercase() {
+            'R' => ChannelKind::Red,
+            'G' => ChannelKind::Green,
+            'B' => ChannelKind::Blue,
+            'I' => ChannelKind::Intensity,
+            'A' => ChannelKind::Alpha,
+            'X' => ChannelKind::Padding,
+            _ => return Err(format!("unknown channel {}", letter)),
+        };
+
+        let mut digits = String::new();
+        while let Some(&digit) = characters.peek() {
+            if !digit.is_ascii_digit() {
+                break;
+            }
+            digits.push(digit);
+            characters.next();
+        }
+        let bits: u32 = match digits.parse() {
+            Ok(bits) if (1..=8).contains(&bits) => bits,
+            Ok(bits) if (1..=32).contains(&bits) && kind == ChannelKind::Padding => bits,
+            _ => return Err(format!("{} needs a size from 1 to 8 bits", letter)),
+        };
+
+        if kind != ChannelKind::Padding && channels.iter().any(|channel| channel.kind == kind) {
+            return Err(format!("{} appears more than once", letter));
+        }
+        channels.push(Channel {
+            kind,
+            bits,
+            shift: 0,
+        });
+    }
+
+    let has = |kind| channels.iter().any(|channel| channel.kind == kind);
+    let has_rgb = has(ChannelKind::Red) && has(ChannelKind::Green) && has(ChannelKind::Blue);
+    let has_any_rgb = has(ChannelKind::Red) || has(ChannelKind::Green) || has(ChannelKind::Blue);
+    if has(ChannelKind::Intensity) == has_any_rgb || (has_any_rgb && !has_rgb) {
+        return Err("a mode needs either R, G and B or I".to_string());
+    }
+
+    let mut shift = channels.iter().map(|channel| channel.bits).sum::<u32>();
+    for channel in &mut channels {
+        shift -= channel.bits;
+        channel.shift = shift;
+    }
+    Ok(Mode { channels })
+}
+
+/// A color in a custom format.
+#[derive(Copy, Clone)]
+pub struct Custom<'f, L: LumaCoefficients> {
+    data: u32,
+    format: &'f Format,
+    luma: PhantomData<L>,
+}
+
+impl<'f, L: LumaCoefficients> Custom<'f, L> {
+    fn in_format(components: (f64, f64, f64, f64), format: &'f Format) -> Custom<'f, L> {
+        if format.modes.len() == 1 {
+            return Custom {
+                data: format.modes[0].encode::<L>(components),
+                format,
+                luma: PhantomData,
+            };
+        }
+
+        let target = Rgba8::new(components, ());
+        let mut best: Option<(f64, Custom<L>)> = None;
+        for (flag, mode) in format.modes.iter().enumerate() {
+            let mut data = mode.encode::<L>(components);
+            if flag == 1 {
+                data |= 1 << format.flag_shift();
+            }
+            let candidate = Custom {
+                data,
+                format,
+                luma: PhantomData,
+            };
+            let distance = target.simple_distance_to(&candidate);
+            if best.map_or(true, |(best_distance, _)| distance < best_distance) {
+                best = Some((distance, candidate));
+            }
+        }
+        best.unwrap().1
+    }
+}
+
+impl<'f, L: LumaCoefficients> Color for Custom<'f, L> {
+    type Context = &'f Format;
+
+    fn new(components: (f64, f64, f64, f64), format: &'f Format) -> Custom<'f, L> {
+        Custom::in_format(components, format)
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let mode = self.format.mode_of(self.data);
+        let (r, g, b, a) = mode.decode(self.data, expand_to_8_bits);
+        Pixel {
+            data: [r, g, b, a.unwrap_or(0xFF)],
+        }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let mode = self.format.mode_of(self.data);
+        let (r, g, b, a) = mode.decode(self.data, |value, bits| {
+            f64::from(value) / f64::from((1u32 << bits) - 1)
+        });
+        (r, g, b, a.unwrap_or(1.0))
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        let size = self.format.native_size();
+        if self.format.little_endian {
+            self.data.to_le_bytes()[..size].to_vec()
+        } else {
+            self.data.to_be_bytes()[4 - size..].to_vec()
+        }
+    }
+
+    /// Bits beyond the format's size are ignored.
+    fn from_native(bytes: &[u8], format: &'f Format) -> Option<Custom<'f, L>> {
+        let size = format.native_size();
+        let mut data_bytes = [0; 4];
+        let data = if format.little_endian {
+            data_bytes[..size].copy_from_slice(&bytes[..size]);
+            u32::from_le_bytes(data_bytes)
+        } else {
+            data_bytes[4 - size..].copy_from_slice(&bytes[..size]);
+            u32::from_be_bytes(data_bytes)
+        };
+        let mask = (!0u32).checked_shr(32 - format.bits).unwrap_or(0);
+        Some(Custom {
+            data: data & mask,
+            format,
+            luma: PhantomData,
+        })
+    }
+
+    fn native_size(format: &'f Format) -> usize {
+        format.native_size()
+    }
+
+    fn native_is_little_endian(format: &'f Format) -> bool {
+        format.little_endian
+    }
+}
+
+// Only colors of the same format are ever compared, so the data is enough.
+impl<'f, L: LumaCoefficients> PartialEq for Custom<'f, L> {
+    fn eq(&self, other: &Custom<'f, L>) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<'f, L: LumaCoefficients> Eq for Custom<'f, L> {}
+
+impl<'f, L: LumaCoefficients> Hash for Custom<'f, L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
+    }
+}
+
+impl<'f, L: LumaCoefficients> fmt::Debug for Custom<'f, L> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Custom")
+            .field("format", &self.format.layout)
+            .field("data", &format_args!("{:#x}", self.data))
+            .finish()
+    }
+}
@@ -0,0 +1,150 @@
+use super::super::*;
+use super::{Custom, Format};
+
+fn test_pixels() -> Vec<Pixel> {
+    let mut pixels = Vec::new();
+    for r in (0..=0xFF).step_by(0x11) {
+        for g in (0..=0xFF).step_by(0x33) {
+            for b in (0..=0xFF).step_by(0x55) {
+                for &a in &[0x00, 0x24, 0x80, 0xDB, 0xFF] {
+                    pixels.push(Pixel {
+                        data: [r as u8, g as u8, b as u8, a],
+                    });
+                }
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn parse_test() {
+    let format = Format::parse("A3R4G4B4|R5G5B5").unwrap();
+    assert_eq!(16, format.bits);
+    assert!(format.has_alpha());
+    assert!(!format.is_grayscale());
+
+    let format = Format::parse("i4a4").unwrap();
+    assert_eq!(8, format.bits);
+    assert!(format.is_grayscale());
+
+    let format = Format::parse("X1B5G5R5:le").unwrap();
+    assert_eq!(16, format.bits);
+    assert!(format.little_endian);
+    assert!(!format.has_alpha());
+}
+
+#[test]
+fn parse_rejects_invalid_layouts() {
+    let layouts = [
+        "",
+        "R5G6",
+        "R5G6B5I4",
+        "R5G6B5R1",
+        "R9G8B8",
+        "R0G8B8",
+        "R5G6B5Q1",
+        "A3R4G4B4|R5G5B4",
+        "R5G5B5|R5G5B5|R5G5B5",
+        "R8G8B8A8X1",
+        "R5G6B5:middle",
+    ];
+    for layout in &layouts {
+        assert!(Format::parse(layout).is_err(), "{} was accepted", layout);
+    }
+}
+
+#[test]
+fn single_mode_matches_built_in_types() {
+    let r5g6b5 = Format::parse("R5G6B5").unwrap();
+    let i4a4 = Format::parse("I4A4").unwrap();
+    for pixel in test_pixels() {
+        let components = Rgba8::from(pixel).components();
+        if pixel[3] == 0xFF {
+            assert_eq!(
+                Rgb565::new(components, ()).as_pixel(),
+                Custom::<Rec601>::in_format(components, &r5g6b5).as_pixel()
+            );
+        }
+        assert_eq!(
+            Ia4::<Rec601>::new(components, ()).as_pixel(),
+            Custom::<Rec601>::in_format(components, &i4a4).as_pixel()
+        );
+    }
+}
+
+#[test]
+fn intensity_uses_the_luma_coefficients() {
+    let i8a8 = Format::parse("I8A8").unwrap();
+    for pixel in test_pixels() {
+        let components = Rgba8::from(pixel).components();
+        assert_eq!(
+            Ia8::<Rec601>::new(components, ()).as_pixel(),
+            Custom::<Rec601>::in_format(components, &i8a8).as_pixel()
+        );
+        assert_eq!(
+            Ia8::<Rec709>::new(components, ()).as_pixel(),
+            Custom::<Rec709>::in_format(components, &i8a8).as_pixel()
+        );
+    }
+}
+
+#[test]
+fn dual_mode_is_as_close_as_rgb5a3() {
+    let format = Format::parse("A3R4G4B4|R5G5B5").unwrap();
+    for pixel in test_pixels() {
+        let original = Rgba8::from(pixel);
+        let custom = Custom::<Rec601>::in_format(original.components(), &format);
+        let rgb5a3 = Rgb5a3::new(original.components(), ());
+        assert!(original.simple_distance_to(&custom) <= original.simple_distance_to(&rgb5a3));
+    }
+}
+
+#[test]
+fn rgb_is_zero_if_alpha_is() {
+    let format = Format::parse("A3R4G4B4|R5G5B5").unwrap();
+    for pixel in test_pixels() {
+        let result =
+            Custom::<Rec601>::in_format(Rgba8::from(pixel).components(), &format).as_pixel();
+        if result.data[3] == 0 {
+            assert_eq!(result.data, [0, 0, 0, 0]);
+        }
+    }
+}
+
+#[test]
+fn native_byte_order_test() {
+    let components = Rgba8::from(Pixel {
+        data: [0x28, 0x50, 0x90, 0xFF],
+    })
+    .components();
+
+    let format = Format::parse("A3R4G4B4|R5G5B5").unwrap();
+    assert_eq!(
+        vec![0x95, 0x52],
+        Custom::<Rec601>::in_format(components, &format).to_native()
+    );
+
+    let format = Format::parse("X1B5G5R5:le").unwrap();
+    assert_eq!(
+        vec![0x45, 0x49],
+        Custom::<Rec601>::in_format(components, &format).to_native()
+    );
+
+    let format = Format::parse("R8G8B8").unwrap();
+    assert_eq!(
+        vec![0x28, 0x50, 0x90],
+        Custom::<Rec601>::in_format(components, &format).to_native()
+    );
+}
+
+#[test]
+fn native_round_trip_test() {
+    let format = Format::parse("A3R4G4B4|R5G5B5").unwrap();
+    for pixel in test_pixels() {
+        let color = Custom::<Rec601>::new(Rgba8::from(pixel).components(), &format);
+        let native = color.to_native();
+        assert_eq!(native.len(), Custom::<Rec601>::native_size(&format));
+        assert_eq!(Some(color), Custom::<Rec601>::from_native(&native, &format));
+    }
+}
@@ -0,0 +1,174 @@
+//! Grayscale colors with alpha, storing a single intensity in place of red,
+//! green and blue.
+//!
+//! Colored input is converted to intensity with the luma coefficients of the
+//! type parameter, so the choice between them is made at compile time like
+//! the rest of the color type.
+
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use super::{expand_to_8_bits, reduce_to_bits, Color, Pixel};
+
+#[cfg(test)]
+mod tests;
+
+/// The weights of red, green and blue in a color's intensity.
+pub trait LumaCoefficients: 'static + Copy + Eq + Hash + fmt::Debug + Send + Sync {
+    const RED: f64;
+    const GREEN: f64;
+    const BLUE: f64;
+
+    fn intensity(r: f64, g: f64, b: f64) -> f64 {
+        (Self::RED * r + Self::GREEN * g + Self::BLUE * b).min(1.0)
+    }
+}
+
+/// Luma as defined for standard definition video.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct Rec601;
+
+impl LumaCoefficients for Rec601 {
+    const RED: f64 = 0.299;
+    const GREEN: f64 = 0.587;
+    const BLUE: f64 = 0.114;
+}
+
+/// Luma as defined for high definition video, and sRGB.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct Rec709;
+
+impl LumaCoefficients for Rec709 {
+    const RED: f64 = 0.2126;
+    const GREEN: f64 = 0.7152;
+    const BLUE: f64 = 0.0722;
+}
+
+/// 8 bits of intensity and 8 bits of alpha.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Ia8<L: LumaCoefficients> {
+    i: u8,
+    a: u8,
+    luma: PhantomData<L>,
+}
+
+impl<L: LumaCoefficients> Color for Ia8<L> {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Ia8<L> {
+        let (r_float, g_float, b_float, a_float) = components;
+
+        let a = (a_float * 255.0).round() as u8;
+        let i = if a == 0 {
+            0
+        } else {
+            (L::intensity(r_float, g_float, b_float) * 255.0).round() as u8
+        };
+        Ia8 {
+            i,
+            a,
+            luma: PhantomData,
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        Pixel {
+            data: [self.i, self.i, self.i, self.a],
+        }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let i = f64::from(self.i) / 255.0;
+        (i, i, i, f64::from(self.a) / 255.0)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        vec![self.a, self.i]
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Ia8<L>> {
+        Some(Ia8 {
+            i: bytes[1],
+            a: bytes[0],
+            luma: PhantomData,
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+}
+
+impl<L: LumaCoefficients> fmt::Debug for Ia8<L> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Ia8")
+            .field("i", &self.i)
+            .field("a", &self.a)
+            .finish()
+    }
+}
+
+/// 4 bits of intensity and 4 bits of alpha.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Ia4<L: LumaCoefficients> {
+    i: u16,
+    a: u16,
+    luma: PhantomData<L>,
+}
+
+impl<L: LumaCoefficients> Color for Ia4<L> {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Ia4<L> {
+        let (r_float, g_float, b_float, a_float) = components;
+
+        let a = reduce_to_bits(a_float, 4);
+        let i = if a == 0 {
+            0
+        } else {
+            reduce_to_bits(L::intensity(r_float, g_float, b_float), 4)
+        };
+        Ia4 {
+            i,
+            a,
+            luma: PhantomData,
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let i = expand_to_8_bits(self.i, 4);
+        let a = expand_to_8_bits(self.a, 4);
+        Pixel { data: [i, i, i, a] }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let i = f64::from(self.i) / 15.0;
+        (i, i, i, f64::from(self.a) / 15.0)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        vec![((self.a << 4) | self.i) as u8]
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Ia4<L>> {
+        Some(Ia4 {
+            i: u16::from(bytes[0] & 0x0F),
+            a: u16::from(bytes[0] >> 4),
+            luma: PhantomData,
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        1
+    }
+}
+
+impl<L: LumaCoefficients> fmt::Debug for Ia4<L> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Ia4")
+            .field("i", &self.i)
+            .field("a", &self.a)
+            .finish()
+    }
+}
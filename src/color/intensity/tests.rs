@@ -0,0 +1,63 @@
+use super::super::*;
+use super::PhantomData;
+
+use k_means::SimpleInput;
+
+#[test]
+fn rgba8_to_ia8_test() {
+    let test_data = [
+        ([0xFF, 0xFF, 0xFF, 0xFF], (0xFF, 0xFF), (0xFF, 0xFF)),
+        ([0xFF, 0x00, 0x00, 0x80], (0x4C, 0x80), (0x36, 0x80)),
+        ([0x00, 0xFF, 0x00, 0xFF], (0x96, 0xFF), (0xB6, 0xFF)),
+        ([0x40, 0x80, 0xC0, 0x00], (0x00, 0x00), (0x00, 0x00)),
+    ];
+    for &(test_data, (rec601_i, rec601_a), (rec709_i, rec709_a)) in &test_data {
+        let pixel = Pixel { data: test_data };
+        let rec601 = ConvertibleColor::<Rgba8, Ia8<Rec601>>::from(pixel).as_output();
+        let rec709 = ConvertibleColor::<Rgba8, Ia8<Rec709>>::from(pixel).as_output();
+        assert_eq!(
+            Pixel {
+                data: [rec601_i, rec601_i, rec601_i, rec601_a]
+            },
+            rec601.as_pixel()
+        );
+        assert_eq!(
+            Pixel {
+                data: [rec709_i, rec709_i, rec709_i, rec709_a]
+            },
+            rec709.as_pixel()
+        );
+    }
+}
+
+#[test]
+fn rgba8_to_ia4_test() {
+    let test_data = [
+        ([0xFF, 0xFF, 0xFF, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]),
+        ([0x80, 0x80, 0x80, 0x80], [0x88, 0x88, 0x88, 0x88]),
+        ([0xFF, 0xFF, 0xFF, 0x08], [0x00, 0x00, 0x00, 0x00]),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Ia4<Rec601>>::from(Pixel { data: test_data });
+        let expected = Pixel {
+            data: expected_data,
+        };
+        let result = test_color.as_output().as_pixel();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn ia4_round_trips_through_pixel() {
+    for i in 0..16 {
+        for a in 1..16 {
+            let color = Ia4::<Rec709> {
+                i,
+                a,
+                luma: PhantomData,
+            };
+            let round_tripped = Ia4::<Rec709>::new(Rgba8::from(color.as_pixel()).components(), ());
+            assert_eq!(color, round_tripped);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Colors limited to a fixed master palette, for hardware like the NES that
+//! can only show a set list of colors.
+//!
+//! Every color snaps to the nearest master palette entry, so cluster centers
+//! are always colors the hardware can show.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::{Color, Pixel, Rgba8};
+
+#[cfg(test)]
+mod tests;
+
+/// The colors a console can show, with the values the hardware uses for them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterPalette {
+    name: String,
+    colors: Vec<Pixel>,
+    /// The hardware's value for each color, such as 0x0F for the NES's black.
+    codes: Vec<u32>,
+}
+
+impl MasterPalette {
+    /// Creates a master palette whose codes are the colors' positions.
+    pub fn new(name: &str, colors: Vec<Pixel>) -> MasterPalette {
+        let codes = (0..colors.len() as u32).collect();
+        MasterPalette::with_codes(name, colors, codes)
+    }
+
+    pub fn with_codes(name: &str, colors: Vec<Pixel>, codes: Vec<u32>) -> MasterPalette {
+        assert_eq!(colors.len(), codes.len());
+        assert!(!colors.is_empty());
+        MasterPalette {
+            name: name.to_string(),
+            colors: colors
+                .into_iter()
+                .map(|color| Pixel {
+                    data: [color[0], color[1], color[2], 0xFF],
+                })
+                .collect(),
+            codes,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// The code of a color from the palette, if it's in it.
+    pub fn code_of(&self, color: Pixel) -> Option<u32> {
+        self.colors
+            .iter()
+            .position(|&entry| entry == color)
+            .map(|index| self.codes[index])
+    }
+
+    fn native_size(&self) -> usize {
+        let largest = self.codes.iter().cloned().max().unwrap_or(0);
+        (4 - largest.leading_zeros() as usize / 8).max(1)
+    }
+
+    fn nearest(&self, components: (f64, f64, f64, f64)) -> usize {
+        let (r, g, b, _a) = components;
+        let target = Rgba8::new((r, g, b, 1.0), ());
+        let distances = self
+            .colors
+            .iter()
+            .map(|&color| target.simple_distance_to(&Rgba8::from(color)));
+        let mut nearest = (0, f64::INFINITY);
+        for (index, distance) in distances.enumerate() {
+            if distance < nearest.1 {
+                nearest = (index, distance);
+            }
+        }
+        nearest.0
+    }
+}
+
+/// An entry of a master palette.
+///
+/// Master palettes are opaque, so images need to be flattened before
+/// converting to it.
+#[derive(Copy, Clone)]
+pub struct Master<'p> {
+    index: usize,
+    palette: &'p MasterPalette,
+}
+
+impl<'p> Master<'p> {
+    fn in_palette(components: (f64, f64, f64, f64), palette: &'p MasterPalette) -> Master<'p> {
+        Master {
+            index: palette.nearest(components),
+            palette,
+        }
+    }
+
+    fn code(self) -> u32 {
+        self.palette.codes[self.index]
+    }
+}
+
+impl<'p> Color for Master<'p> {
+    type Context = &'p MasterPalette;
+
+    fn new(components: (f64, f64, f64, f64), palette: &'p MasterPalette) -> Master<'p> {
+        Master::in_palette(components, palette)
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        self.palette.colors[self.index]
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        Rgba8::from(self.as_pixel()).components()
+    }
+
+    /// The color's code, in as few bytes as the palette's largest code needs.
+    fn to_native(&self) -> Vec<u8> {
+        let size = self.palette.native_size();
+        self.code().to_be_bytes()[4 - size..].to_vec()
+    }
+
+    /// None if the code isn't one of the palette's.
+    fn from_native(bytes: &[u8], palette: &'p MasterPalette) -> Option<Master<'p>> {
+        let size = palette.native_size();
+        let mut code_bytes = [0; 4];
+        code_bytes[4 - size..].copy_from_slice(&bytes[..size]);
+        let code = u32::from_be_bytes(code_bytes);
+        palette
+            .codes
+            .iter()
+            .position(|&other| other == code)
+            .map(|index| Master { index, palette })
+    }
+
+    fn native_size(palette: &'p MasterPalette) -> usize {
+        palette.native_size()
+    }
+}
+
+// Only colors of the same palette are ever compared, so the index is enough.
+impl<'p> PartialEq for Master<'p> {
+    fn eq(&self, other: &Master<'p>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<'p> Eq for Master<'p> {}
+
+impl<'p> Hash for Master<'p> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<'p> fmt::Debug for Master<'p> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Master")
+            .field("palette", &self.palette.name)
+            .field("code", &format_args!("{:#04x}", self.code()))
+            .finish()
+    }
+}
@@ -0,0 +1,67 @@
+use super::super::*;
+use super::{Master, MasterPalette};
+
+fn test_palette() -> MasterPalette {
+    let colors = [[0x00, 0x00, 0x00], [0xFF, 0x00, 0x00], [0xFF, 0xFF, 0xFF]]
+        .iter()
+        .map(|&[r, g, b]| Pixel {
+            data: [r, g, b, 0xFF],
+        })
+        .collect();
+    MasterPalette::with_codes("test", colors, vec![0x0F, 0x16, 0x130])
+}
+
+#[test]
+fn snaps_to_nearest_entry() {
+    let palette = &test_palette();
+    let test_data = [
+        ([0x10, 0x08, 0x00, 0xFF], [0x00, 0x00, 0x00, 0xFF]),
+        ([0xC0, 0x30, 0x20, 0xFF], [0xFF, 0x00, 0x00, 0xFF]),
+        ([0xC0, 0xC0, 0xA0, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let components = Rgba8::from(Pixel { data: test_data }).components();
+        let result = Master::in_palette(components, palette).as_pixel();
+        assert_eq!(expected_data, result.data);
+    }
+}
+
+#[test]
+fn entries_round_trip_through_components() {
+    let palette = &test_palette();
+    for index in 0..palette.len() {
+        let color = Master { index, palette };
+        assert_eq!(color, Master::in_palette(color.components(), palette));
+    }
+}
+
+#[test]
+fn native_is_code() {
+    let palette = &test_palette();
+    assert_eq!(vec![0x00, 0x0F], Master { index: 0, palette }.to_native());
+    assert_eq!(vec![0x01, 0x30], Master { index: 2, palette }.to_native());
+
+    let colors = vec![Pixel { data: [0; 4] }; 4];
+    let small = MasterPalette::new("small", colors);
+    assert_eq!(
+        vec![0x03],
+        Master {
+            index: 3,
+            palette: &small
+        }
+        .to_native()
+    );
+}
+
+#[test]
+fn code_of_test() {
+    let palette = &test_palette();
+    let red = Pixel {
+        data: [0xFF, 0x00, 0x00, 0xFF],
+    };
+    let gray = Pixel {
+        data: [0x80, 0x80, 0x80, 0xFF],
+    };
+    assert_eq!(Some(0x16), palette.code_of(red));
+    assert_eq!(None, palette.code_of(gray));
+}
@@ -1,16 +1,31 @@
 //! This implements k-means traits for colors and other support functions.
 
-use std::marker::PhantomData;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 
 use image_lib;
 use num::{FromPrimitive, Zero};
 
 use k_means::{Grouped, Input, Output, SimpleInput};
 
+mod argb1555;
+mod bgr555;
 pub mod combination;
+mod custom;
+mod intensity;
+mod master;
+mod rgb565;
 mod rgb5a3;
+mod rgba4444;
 mod rgba8;
+pub use self::argb1555::Argb1555;
+pub use self::bgr555::{Abgr1555, Bgr555};
+pub use self::custom::{Custom, Format};
+pub use self::intensity::{Ia4, Ia8, LumaCoefficients, Rec601, Rec709};
+pub use self::master::{Master, MasterPalette};
+pub use self::rgb565::Rgb565;
 pub use self::rgb5a3::Rgb5a3;
+pub use self::rgba4444::Rgba4444;
 pub use self::rgba8::Rgba8;
 
 #[cfg(test)]
@@ -18,24 +33,95 @@ mod tests;
 
 pub type Pixel = image_lib::Rgba<u8>;
 
-pub trait Color: Output {
-    fn new(components: (f64, f64, f64, f64)) -> Self;
+/// How colors with transparency are compared: how much a difference in alpha
+/// counts, and whether to compare premultiplied colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlphaDistance {
+    pub weight: f64,
+    pub premultiplied: bool,
+}
+
+impl AlphaDistance {
+    /// The distance between two colors.
+    pub fn between<A: Color, B: Color>(self, first: &A, second: &B) -> f64 {
+        components_distance(first.components(), second.components(), self)
+    }
+}
+
+/// A difference in alpha counts 3 times as much as one in a color component,
+/// and colors aren't premultiplied.
+impl Default for AlphaDistance {
+    fn default() -> AlphaDistance {
+        AlphaDistance {
+            weight: 3.0,
+            premultiplied: false,
+        }
+    }
+}
+
+/// The distance between two colors' components. Normally the difference in
+/// color is scaled by both alphas, so it counts less the more transparent
+/// either color is; premultiplied, each color is scaled by its own alpha
+/// before they're compared.
+pub fn components_distance(
+    (r1, g1, b1, a1): (f64, f64, f64, f64),
+    (r2, g2, b2, a2): (f64, f64, f64, f64),
+    alpha_distance: AlphaDistance,
+) -> f64 {
+    let color_distance = if alpha_distance.premultiplied {
+        (r1 * a1 - r2 * a2).powi(2) + (g1 * a1 - g2 * a2).powi(2) + (b1 * a1 - b2 * a2).powi(2)
+    } else {
+        let opaque_distance = (r1 - r2).powi(2) + (g1 - g2).powi(2) + (b1 - b2).powi(2);
+        opaque_distance * a1 * a2
+    };
+    color_distance + (a1 - a2).powi(2) * alpha_distance.weight
+}
+
+pub trait Color: Output + Send + Sync {
+    /// What's needed besides the components to create a color, such as the
+    /// format of a custom color. The built-in color types need nothing.
+    type Context: Copy + Debug + Send + Sync;
+
+    fn new(components: (f64, f64, f64, f64), context: Self::Context) -> Self;
 
     fn as_pixel(&self) -> Pixel;
 
     fn components(&self) -> (f64, f64, f64, f64);
-    fn simple_distance_to<T: Color>(&self, other: &T) -> Self::Distance {
-        let (r1, g1, b1, a1) = self.components();
-        let (r2, g2, b2, a2) = other.components();
 
-        let opaque_distance = (r1 - r2).powi(2) + (g1 - g2).powi(2) + (b1 - b2).powi(2);
-        let alpha_distance = (a1 - a2).powi(2) * 3.0;
+    /// The color as it's stored in a palette on the hardware that uses it.
+    fn to_native(&self) -> Vec<u8>;
+
+    /// Reads a color written by `to_native`, or None if the bytes aren't a
+    /// valid color. There must be at least `native_size` bytes.
+    fn from_native(bytes: &[u8], context: Self::Context) -> Option<Self>;
+
+    /// The number of bytes `to_native` writes.
+    fn native_size(context: Self::Context) -> usize;
 
-        let distance = (opaque_distance * a1 * a2) + alpha_distance;
+    /// Whether `to_native` writes the color as a little-endian number.
+    fn native_is_little_endian(_context: Self::Context) -> bool {
+        false
+    }
+
+    /// The distance to another color, comparing transparency the default way.
+    fn simple_distance_to<T: Color>(&self, other: &T) -> Self::Distance {
+        let distance = AlphaDistance::default().between(self, other);
         Self::Distance::from_f64(distance).unwrap()
     }
 }
 
+/// Expands an n-bit channel value to 8 bits, rounding to the nearest value.
+fn expand_to_8_bits(value: u16, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+    ((u32::from(value) * 255 + max / 2) / max) as u8
+}
+
+/// Reduces a component from the range 0 to 1 to an n-bit channel value.
+fn reduce_to_bits(component: f64, bits: u32) -> u16 {
+    let max = f64::from((1u32 << bits) - 1);
+    (component * max).round() as u16
+}
+
 impl<T: Color> Output for T {
     type Distance = f64;
     fn distance_to(&self, other: &T) -> Self::Distance {
@@ -43,47 +129,87 @@ impl<T: Color> Output for T {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+/// A color along with what's needed to convert it to the output color type.
+#[derive(Copy, Clone, Debug)]
 pub struct ConvertibleColor<I: Color, O: Color> {
     pub color: I,
-    output_type: PhantomData<O>,
+    output_context: O::Context,
 }
 
-impl<O: Color> From<Pixel> for ConvertibleColor<Rgba8, O> {
-    fn from(pixel: Pixel) -> Self {
+impl<O: Color> ConvertibleColor<Rgba8, O> {
+    pub fn new(pixel: Pixel, output_context: O::Context) -> Self {
         ConvertibleColor {
             color: pixel.into(),
-            output_type: PhantomData,
+            output_context,
         }
     }
 }
 
-impl<I: Color, O: Color> SimpleInput for ConvertibleColor<I, O> {
-    type Output = O;
-    type Distance = I::Distance;
-
-    fn distance_to(&self, other: &Self::Output) -> Self::Distance {
-        self.color.simple_distance_to(other)
+impl<I: Color, O: Color> ConvertibleColor<I, O> {
+    /// The distance to an output color, comparing transparency as given.
+    pub fn distance_with(&self, other: &O, alpha_distance: AlphaDistance) -> I::Distance {
+        I::Distance::from_f64(alpha_distance.between(&self.color, other)).unwrap()
     }
 
-    fn normalized_distance(&self, other: &Self::Output) -> Self::Distance {
+    /// How much farther an output color is than the color converted to the
+    /// output type, which is as close as it can get.
+    pub fn normalized_distance_with(
+        &self,
+        other: &O,
+        alpha_distance: AlphaDistance,
+    ) -> I::Distance {
         let output = self.as_output();
-        let closest_possible_distance = self.distance_to(&output);
-        let distance = self.distance_to(other);
+        let closest_possible_distance = self.distance_with(&output, alpha_distance);
+        let distance = self.distance_with(other, alpha_distance);
 
         if distance < closest_possible_distance {
             println!(
                 "Distance from {:?} to {:?} is closer than to output version {:?}",
                 self, other, output
             );
-            return Self::Distance::zero();
+            return I::Distance::zero();
         }
 
         distance - closest_possible_distance
     }
+}
+
+impl<O: Color<Context = ()>> From<Pixel> for ConvertibleColor<Rgba8, O> {
+    fn from(pixel: Pixel) -> Self {
+        ConvertibleColor::new(pixel, ())
+    }
+}
+
+// Only colors converting to the same output are ever compared, so the color is
+// enough.
+impl<I: Color, O: Color> PartialEq for ConvertibleColor<I, O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.color == other.color
+    }
+}
+
+impl<I: Color, O: Color> Eq for ConvertibleColor<I, O> {}
+
+impl<I: Color, O: Color> Hash for ConvertibleColor<I, O> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.color.hash(state);
+    }
+}
+
+impl<I: Color, O: Color> SimpleInput for ConvertibleColor<I, O> {
+    type Output = O;
+    type Distance = I::Distance;
+
+    fn distance_to(&self, other: &Self::Output) -> Self::Distance {
+        self.distance_with(other, AlphaDistance::default())
+    }
+
+    fn normalized_distance(&self, other: &Self::Output) -> Self::Distance {
+        self.normalized_distance_with(other, AlphaDistance::default())
+    }
 
     fn as_output(&self) -> Self::Output {
-        Self::Output::new(self.color.components())
+        Self::Output::new(self.color.components(), self.output_context)
     }
 }
 
@@ -93,13 +219,14 @@ impl<I: Color, O: Color> Input for Grouped<ConvertibleColor<I, O>> {
             grouped_colors
                 .iter()
                 .map(|&group| (&group.data, group.count)),
+            grouped_colors[0].data.output_context,
         )
     }
 }
 
-fn mean_of_colors<'a, I, C, O>(colors_with_counts: I) -> O
+fn mean_of_colors<'a, I, C, O>(colors_with_counts: I, context: O::Context) -> O
 where
-    I: Iterator<Item = (&'a ConvertibleColor<C, O>, u32)>,
+    I: Iterator<Item = (&'a ConvertibleColor<C, O>, u64)>,
     C: 'a + Color,
     O: 'a + Color,
 {
@@ -111,7 +238,7 @@ where
 
     for (data, count) in colors_with_counts {
         let (r, g, b, a) = data.color.components();
-        let weighted_a = a * f64::from(count);
+        let weighted_a = a * count as f64;
 
         r_sum += r * weighted_a;
         g_sum += g * weighted_a;
@@ -124,10 +251,10 @@ where
         let r = r_sum / a_sum;
         let g = g_sum / a_sum;
         let b = b_sum / a_sum;
-        let a = a_sum / f64::from(total_count);
+        let a = a_sum / total_count as f64;
 
-        O::new((r, g, b, a))
+        O::new((r, g, b, a), context)
     } else {
-        O::new((0.0, 0.0, 0.0, 0.0))
+        O::new((0.0, 0.0, 0.0, 0.0), context)
     }
 }
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use super::{expand_to_8_bits, reduce_to_bits, Color, Pixel};
+
+#[cfg(test)]
+mod tests;
+
+/// A 16 bit opaque color, with 5 bits of red, 6 of green and 5 of blue.
+///
+/// There's no alpha, so images need to be flattened before converting to it.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Rgb565 {
+    data: u16,
+}
+
+impl Rgb565 {
+    fn r5(self) -> u16 {
+        (self.data >> 11) & 0x1F
+    }
+    fn g6(self) -> u16 {
+        (self.data >> 5) & 0x3F
+    }
+    fn b5(self) -> u16 {
+        self.data & 0x1F
+    }
+}
+
+impl Color for Rgb565 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Rgb565 {
+        let (r_float, g_float, b_float, _a_float) = components;
+        let r = reduce_to_bits(r_float, 5);
+        let g = reduce_to_bits(g_float, 6);
+        let b = reduce_to_bits(b_float, 5);
+        Rgb565 {
+            data: (r << 11) | (g << 5) | b,
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let r = expand_to_8_bits(self.r5(), 5);
+        let g = expand_to_8_bits(self.g6(), 6);
+        let b = expand_to_8_bits(self.b5(), 5);
+        Pixel {
+            data: [r, g, b, 0xFF],
+        }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let r = f64::from(self.r5()) / 31.0;
+        let g = f64::from(self.g6()) / 63.0;
+        let b = f64::from(self.b5()) / 31.0;
+        (r, g, b, 1.0)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_be_bytes().to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Rgb565> {
+        Some(Rgb565 {
+            data: u16::from_be_bytes([bytes[0], bytes[1]]),
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+}
+
+impl fmt::Debug for Rgb565 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Rgb565")
+            .field("r", &self.r5())
+            .field("g", &self.g6())
+            .field("b", &self.b5())
+            .finish()
+    }
+}
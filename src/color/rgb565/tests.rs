@@ -0,0 +1,61 @@
+use super::super::*;
+
+use k_means::SimpleInput;
+
+#[test]
+fn rgba8_to_rgb565_test() {
+    let test_data = [
+        ([0xFF, 0x00, 0x08, 0xFF], (0x1F << 11) | (0 << 5) | 1),
+        ([0xED, 0x04, 0x05, 0xFF], (0x1D << 11) | (1 << 5) | 1),
+        ([0x80, 0x80, 0x80, 0xFF], (0x10 << 11) | (0x20 << 5) | 0x10),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Rgb565>::from(Pixel { data: test_data });
+        let expected = Rgb565 {
+            data: expected_data,
+        };
+        let result = test_color.as_output();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn rgb565_as_pixel_test() {
+    let test_data = [
+        ((0x1F << 11) | (0 << 5) | 1, [0xFF, 0x00, 0x08, 0xFF]),
+        ((0x1D << 11) | (1 << 5) | 1, [0xEF, 0x04, 0x08, 0xFF]),
+        ((0x10 << 11) | (0x20 << 5) | 0x10, [0x84, 0x82, 0x84, 0xFF]),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = Rgb565 { data: test_data };
+        let expected = Pixel {
+            data: expected_data,
+        };
+        let result = test_color.as_pixel();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn rgb565_round_trips_through_pixel() {
+    for data in (0..=0xFFFFu32).step_by(7) {
+        let color = Rgb565 { data: data as u16 };
+        let round_tripped = Rgb565::new(Rgba8::from(color.as_pixel()).components(), ());
+        assert_eq!(color, round_tripped);
+    }
+}
+
+#[test]
+fn expand_to_8_bits_test() {
+    let test_data = [
+        ((0, 6), 0),
+        ((1, 6), 4),
+        ((0x3F, 6), 0xFF),
+        ((1, 5), 8),
+        ((0x1F, 5), 0xFF),
+    ];
+    for &((value, bits), expected_result) in &test_data {
+        let actual_result = expand_to_8_bits(value, bits);
+        assert_eq!(expected_result, actual_result);
+    }
+}
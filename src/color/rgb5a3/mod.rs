@@ -59,7 +59,9 @@ fn convert_3_bits_to_8(byte: u16) -> u8 {
 }
 
 impl Color for Rgb5a3 {
-    fn new(components: (f64, f64, f64, f64)) -> Rgb5a3 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Rgb5a3 {
         let (r_float, g_float, b_float, a_float) = components;
         let a = (a_float * 7.0).round() as u16;
 
@@ -122,6 +124,20 @@ impl Color for Rgb5a3 {
             }
         }
     }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_be_bytes().to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Rgb5a3> {
+        Some(Rgb5a3 {
+            data: u16::from_be_bytes([bytes[0], bytes[1]]),
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
 }
 
 impl fmt::Debug for Rgb5a3 {
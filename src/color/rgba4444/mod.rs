@@ -0,0 +1,93 @@
+use std::fmt;
+
+use super::{expand_to_8_bits, reduce_to_bits, Color, Pixel};
+
+#[cfg(test)]
+mod tests;
+
+/// A 16 bit color with 4 bits each of red, green, blue and alpha, from high
+/// bits to low.
+#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Rgba4444 {
+    data: u16,
+}
+
+impl Rgba4444 {
+    fn r4(self) -> u16 {
+        (self.data >> 12) & 0x0F
+    }
+    fn g4(self) -> u16 {
+        (self.data >> 8) & 0x0F
+    }
+    fn b4(self) -> u16 {
+        (self.data >> 4) & 0x0F
+    }
+    fn a4(self) -> u16 {
+        self.data & 0x0F
+    }
+}
+
+impl Color for Rgba4444 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Rgba4444 {
+        let (r_float, g_float, b_float, a_float) = components;
+
+        let a = reduce_to_bits(a_float, 4);
+        if a == 0 {
+            return Rgba4444 { data: 0 };
+        }
+
+        let r = reduce_to_bits(r_float, 4);
+        let g = reduce_to_bits(g_float, 4);
+        let b = reduce_to_bits(b_float, 4);
+        Rgba4444 {
+            data: (r << 12) | (g << 8) | (b << 4) | a,
+        }
+    }
+
+    fn as_pixel(&self) -> Pixel {
+        let r = expand_to_8_bits(self.r4(), 4);
+        let g = expand_to_8_bits(self.g4(), 4);
+        let b = expand_to_8_bits(self.b4(), 4);
+        let a = expand_to_8_bits(self.a4(), 4);
+        Pixel { data: [r, g, b, a] }
+    }
+
+    fn components(&self) -> (f64, f64, f64, f64) {
+        let r = f64::from(self.r4()) / 15.0;
+        let g = f64::from(self.g4()) / 15.0;
+        let b = f64::from(self.b4()) / 15.0;
+        let a = f64::from(self.a4()) / 15.0;
+        (r, g, b, a)
+    }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.to_le_bytes().to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Rgba4444> {
+        Some(Rgba4444 {
+            data: u16::from_le_bytes([bytes[0], bytes[1]]),
+        })
+    }
+
+    fn native_size(_: ()) -> usize {
+        2
+    }
+
+    fn native_is_little_endian(_: ()) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Rgba4444 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Rgba4444")
+            .field("r", &self.r4())
+            .field("g", &self.g4())
+            .field("b", &self.b4())
+            .field("a", &self.a4())
+            .finish()
+    }
+}
@@ -0,0 +1,82 @@
+use super::super::*;
+
+use k_means::SimpleInput;
+
+#[test]
+fn rgba8_to_rgba4444_test() {
+    let test_data = [
+        (
+            [0xFF, 0x00, 0x08, 0xFF],
+            (0xF << 12) | (0 << 8) | (0 << 4) | 0xF,
+        ),
+        (
+            [0xED, 0x04, 0x05, 0xED],
+            (0xE << 12) | (0 << 8) | (0 << 4) | 0xE,
+        ),
+        (
+            [0x40, 0x80, 0xC0, 0x44],
+            (0x4 << 12) | (0x8 << 8) | (0xB << 4) | 0x4,
+        ),
+        ([0x40, 0x80, 0xC0, 0x08], 0),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = ConvertibleColor::<Rgba8, Rgba4444>::from(Pixel { data: test_data });
+        let expected = Rgba4444 {
+            data: expected_data,
+        };
+        let result = test_color.as_output();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn rgba4444_as_pixel_test() {
+    let test_data = [
+        (
+            (0xF << 12) | (0 << 8) | (0 << 4) | 0xF,
+            [0xFF, 0x00, 0x00, 0xFF],
+        ),
+        (
+            (0xE << 12) | (0 << 8) | (0x8 << 4) | 0xE,
+            [0xEE, 0x00, 0x88, 0xEE],
+        ),
+        (
+            (0x1 << 12) | (0x2 << 8) | (0x3 << 4) | 0x4,
+            [0x11, 0x22, 0x33, 0x44],
+        ),
+    ];
+    for &(test_data, expected_data) in &test_data {
+        let test_color = Rgba4444 { data: test_data };
+        let expected = Pixel {
+            data: expected_data,
+        };
+        let result = test_color.as_pixel();
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn rgba4444_round_trips_through_pixel() {
+    for data in (0..=0xFFFFu32).filter(|data| data & 0x0F != 0) {
+        let color = Rgba4444 { data: data as u16 };
+        let round_tripped = Rgba4444::new(Rgba8::from(color.as_pixel()).components(), ());
+        assert_eq!(color, round_tripped);
+    }
+}
+
+#[test]
+fn rgba4444_rgb_is_zero_if_alpha_is() {
+    for alpha in 0..=0xFF {
+        for &rgb in &[[0xFF, 0xFF, 0xFF], [0x40, 0x80, 0xC0]] {
+            let pixel = Pixel {
+                data: [rgb[0], rgb[1], rgb[2], alpha],
+            };
+            let result = ConvertibleColor::<Rgba8, Rgba4444>::from(pixel)
+                .as_output()
+                .as_pixel();
+            if result.data[3] == 0 {
+                assert_eq!(result.data, [0, 0, 0, 0]);
+            }
+        }
+    }
+}
@@ -8,7 +8,9 @@ pub struct Rgba8 {
 }
 
 impl Color for Rgba8 {
-    fn new(components: (f64, f64, f64, f64)) -> Rgba8 {
+    type Context = ();
+
+    fn new(components: (f64, f64, f64, f64), _: ()) -> Rgba8 {
         let (r_float, g_float, b_float, a_float) = components;
 
         let a = (a_float * 255.0).round() as u8;
@@ -39,6 +41,21 @@ impl Color for Rgba8 {
             f64::from(a) / 255.0,
         )
     }
+
+    fn to_native(&self) -> Vec<u8> {
+        self.data.data.to_vec()
+    }
+
+    fn from_native(bytes: &[u8], _: ()) -> Option<Rgba8> {
+        let pixel = Pixel {
+            data: [bytes[0], bytes[1], bytes[2], bytes[3]],
+        };
+        Some(pixel.into())
+    }
+
+    fn native_size(_: ()) -> usize {
+        4
+    }
 }
 
 impl From<Pixel> for Rgba8 {
@@ -1,5 +1,6 @@
 use super::*;
-use k_means::{Grouped, Input};
+use color::combination::{ColorCombination, ConvertibleColorCombination, Metric};
+use k_means::{Grouped, Input, SimpleInput};
 
 #[test]
 fn color_distance_test() {
@@ -15,6 +16,33 @@ fn color_distance_test() {
     }
 }
 
+#[test]
+fn straight_distance_scales_the_color_difference_by_both_alphas() {
+    let white = (1.0, 1.0, 1.0, 1.0);
+    let half_white = (1.0, 1.0, 1.0, 0.5);
+    let half_black = (0.0, 0.0, 0.0, 0.5);
+    let alpha_distance = AlphaDistance {
+        weight: 1.0,
+        premultiplied: false,
+    };
+    assert_eq!(components_distance(white, half_white, alpha_distance), 0.25);
+    assert_eq!(
+        components_distance(half_white, half_black, alpha_distance),
+        0.75
+    );
+}
+
+#[test]
+fn premultiplied_distance_scales_each_color_by_its_alpha() {
+    let white = (1.0, 1.0, 1.0, 1.0);
+    let half_white = (1.0, 1.0, 1.0, 0.5);
+    let alpha_distance = AlphaDistance {
+        weight: 2.0,
+        premultiplied: true,
+    };
+    assert_eq!(components_distance(white, half_white, alpha_distance), 1.25);
+}
+
 #[test]
 fn color_mean_test() {
     let test_data = [
@@ -48,3 +76,58 @@ fn color_mean_test() {
         assert_eq!(expected_mean, result);
     }
 }
+
+#[test]
+fn weighted_sum_scales_each_image() {
+    let distances = [1.0, 2.0, 4.0];
+    assert_eq!(
+        7.0,
+        combination::weighted_sum(distances.iter().cloned(), None)
+    );
+    assert_eq!(
+        5.5,
+        combination::weighted_sum(distances.iter().cloned(), Some(&[2.0, 1.0, 0.375]))
+    );
+}
+
+#[test]
+fn combination_distance_uses_its_metric_weights() {
+    let black = Pixel {
+        data: [0x00, 0x00, 0x00, 0xFF],
+    };
+    let white = Pixel {
+        data: [0xFF, 0xFF, 0xFF, 0xFF],
+    };
+    let metric = Metric {
+        image_weights: Some(vec![2.0, 0.5]),
+        ..Metric::default()
+    };
+    let combination =
+        ConvertibleColorCombination::<Rgba8, Rgba8>::new(vec![black.into(), black.into()], &metric);
+
+    let center = ColorCombination::new(vec![white.into(), black.into()], &metric);
+    assert_eq!(6.0, combination.distance_to(&center));
+    let center = ColorCombination::new(vec![black.into(), white.into()], &metric);
+    assert_eq!(1.5, combination.distance_to(&center));
+}
+
+#[test]
+fn combination_distance_compares_alpha_as_its_metric_says() {
+    let white = Pixel {
+        data: [0xFF, 0xFF, 0xFF, 0xFF],
+    };
+    let clear = Pixel {
+        data: [0x00, 0x00, 0x00, 0x00],
+    };
+    let metric = Metric {
+        alpha: AlphaDistance {
+            weight: 0.5,
+            premultiplied: false,
+        },
+        ..Metric::default()
+    };
+    let combination = ConvertibleColorCombination::<Rgba8, Rgba8>::new(vec![white.into()], &metric);
+    let center = ColorCombination::new(vec![clear.into()], &metric);
+    assert_eq!(0.5, combination.distance_to(&center));
+    assert_eq!(0.5, center.distance_to(&combination.as_output()));
+}
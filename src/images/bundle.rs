@@ -0,0 +1,124 @@
+//! Writes a set of images as one index image and a palette for each, so the
+//! indexes they share are only stored once.
+//!
+//! A JSON manifest lists the files, and which palette goes with which input
+//! image.
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use png;
+use png::HasParameters;
+
+use color::Pixel;
+use json;
+use options::{PaletteFormat, Settings};
+use palette;
+
+use super::derived_path;
+
+/// The files written for each image, named relative to the manifest.
+pub struct BundledImage {
+    pub source: String,
+    pub palette: String,
+    pub native_palette: Option<String>,
+}
+
+/// Writes the indexes as a grayscale PNG named after the first output image,
+/// each palette as hex next to its output image, and the manifest.
+pub fn write_bundle(
+    input_paths: &[&Path],
+    output_paths: &[&Path],
+    indexed_image_data: &[u8],
+    palettes: &[Vec<Pixel>],
+    width: u32,
+    height: u32,
+    settings: &Settings,
+) -> io::Result<()> {
+    let index_path = derived_path(output_paths[0], "indexes", "png");
+    write_grayscale_png(&index_path, indexed_image_data, width, height)?;
+
+    let mut images = Vec::with_capacity(palettes.len());
+    for ((input_path, output_path), colors) in input_paths
+        .iter()
+        .zip(output_paths.iter())
+        .zip(palettes.iter())
+    {
+        let palette_path = derived_path(output_path, "palette", "hex");
+        palette::write(&palette_path, PaletteFormat::Hex, colors)?;
+        let native_palette_path = if settings.raw_palette {
+            Some(file_name(&derived_path(output_path, "palette", "bin")))
+        } else {
+            None
+        };
+        images.push(BundledImage {
+            source: input_path.display().to_string(),
+            palette: file_name(&palette_path),
+            native_palette: native_palette_path,
+        });
+    }
+
+    let manifest = manifest(
+        &file_name(&index_path),
+        width,
+        height,
+        palettes[0].len(),
+        settings.colortype.name(),
+        &images,
+    );
+    let mut file = File::create(derived_path(output_paths[0], "manifest", "json"))?;
+    writeln!(file, "{}", manifest)
+}
+
+pub fn manifest(
+    index_file: &str,
+    width: u32,
+    height: u32,
+    num_colors: usize,
+    color_type: &str,
+    images: &[BundledImage],
+) -> String {
+    let images = json::array(images.iter().map(|image| {
+        let native_palette = image
+            .native_palette
+            .as_ref()
+            .map_or_else(String::new, |path| {
+                format!(", \"native_palette\": {}", json::string(path))
+            });
+        format!(
+            "{{\"source\": {}, \"palette\": {}{}}}",
+            json::string(&image.source),
+            json::string(&image.palette),
+            native_palette
+        )
+    }));
+    format!(
+        "{{\"indexes\": {}, \"width\": {}, \"height\": {}, \"colors\": {}, \
+         \"color_type\": {}, \"images\": {}}}",
+        json::string(index_file),
+        width,
+        height,
+        num_colors,
+        json::string(color_type),
+        images
+    )
+}
+
+fn write_grayscale_png(path: &Path, data: &[u8], width: u32, height: u32) -> io::Result<()> {
+    let output = File::create(path)?;
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder
+        .set(png::ColorType::Grayscale)
+        .set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    Ok(())
+}
+
+/// The name of a file in the same directory as the manifest.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
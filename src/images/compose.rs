@@ -0,0 +1,76 @@
+//! Rebuilds full color images from indexes and a palette, to check that
+//! stored indexes and palettes round-trip, or to preview a palette swap.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use image_lib::RgbaImage;
+
+use png;
+use png::HasParameters;
+
+use color::Pixel;
+
+use super::raw;
+
+/// Indexes with the width and height of the image they're for.
+type Indexes = (Vec<u8>, u32, u32);
+
+/// Reads indexes from an 8 bit indexed or grayscale PNG, such as output or an
+/// index image from `--bundle`, or from raw indexes written with a header.
+pub fn read_indexes(path: &Path) -> io::Result<Indexes> {
+    let is_png = path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("png"));
+    if is_png {
+        read_png_indexes(path)
+    } else {
+        raw::read_index_data(&fs::read(path)?)
+            .map_err(|error| invalid_indexes(path, &error.to_string()))
+    }
+}
+
+fn invalid_indexes(path: &Path, message: &str) -> io::Error {
+    let message = format!("{}: {}", path.display(), message);
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_png_indexes(path: &Path) -> io::Result<Indexes> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info()?;
+    let is_index_image =
+        info.color_type == png::ColorType::Indexed || info.color_type == png::ColorType::Grayscale;
+    if !is_index_image || info.bit_depth != png::BitDepth::Eight {
+        return Err(invalid_indexes(
+            path,
+            "indexes must be an 8 bit indexed or grayscale PNG",
+        ));
+    }
+
+    let mut indexes = vec![0; info.buffer_size()];
+    reader.next_frame(&mut indexes)?;
+    Ok((indexes, info.width, info.height))
+}
+
+/// Looks up each index in the palette.
+pub fn compose_image(
+    indexes: &[u8],
+    width: u32,
+    height: u32,
+    palette: &[Pixel],
+) -> Result<RgbaImage, String> {
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, &index) in image.pixels_mut().zip(indexes.iter()) {
+        *pixel = *palette.get(index as usize).ok_or_else(|| {
+            format!(
+                "index {} is past the end of the palette of {} colors",
+                index,
+                palette.len()
+            )
+        })?;
+    }
+    Ok(image)
+}
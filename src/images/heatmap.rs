@@ -0,0 +1,72 @@
+//! Images showing where quantization changed the colors the most.
+
+use image_lib::{GenericImage, ImageError, RgbaImage};
+
+use color::{AlphaDistance, Pixel, Rgba8};
+
+/// Draws the distance between the original and quantized color of each pixel,
+/// using the same distance as clustering.
+///
+/// Errors are scaled so that `max_error` is white, going through red and
+/// yellow from black at zero.
+pub fn heatmap(
+    original: &RgbaImage,
+    quantized: &RgbaImage,
+    max_error: f64,
+    alpha_distance: AlphaDistance,
+) -> RgbaImage {
+    RgbaImage::from_fn(original.width(), original.height(), |x, y| {
+        let original_color = Rgba8::from(*original.get_pixel(x, y));
+        let quantized_color = Rgba8::from(*quantized.get_pixel(x, y));
+        let error = alpha_distance.between(&original_color, &quantized_color);
+        let scaled = if max_error > 0.0 {
+            error / max_error
+        } else {
+            0.0
+        };
+        heat_color(scaled)
+    })
+}
+
+/// The largest error between any of the original and quantized images.
+pub fn max_error(
+    originals: &[RgbaImage],
+    quantized_images: &[RgbaImage],
+    alpha_distance: AlphaDistance,
+) -> f64 {
+    originals
+        .iter()
+        .zip(quantized_images.iter())
+        .flat_map(|(original, quantized)| {
+            original
+                .pixels()
+                .zip(quantized.pixels())
+                .map(move |(&o, &q)| alpha_distance.between(&Rgba8::from(o), &Rgba8::from(q)))
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Places the original, quantized and heatmap images side by side.
+pub fn composite(
+    original: &RgbaImage,
+    quantized: &RgbaImage,
+    heatmap: &RgbaImage,
+) -> Result<RgbaImage, ImageError> {
+    let width = original.width();
+    let mut composite = RgbaImage::new(width * 3, original.height());
+    for (i, image) in [original, quantized, heatmap].iter().enumerate() {
+        if !composite.copy_from(*image, width * i as u32, 0) {
+            return Err(ImageError::DimensionError);
+        }
+    }
+    Ok(composite)
+}
+
+/// Black at 0, going through red and yellow to white at 1.
+pub fn heat_color(value: f64) -> Pixel {
+    let value = value.clamp(0.0, 1.0) * 3.0;
+    let channel = |offset: f64| ((value - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    Pixel {
+        data: [channel(0.0), channel(1.0), channel(2.0), 0xFF],
+    }
+}
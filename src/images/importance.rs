@@ -0,0 +1,157 @@
+//! How much each pixel counts when clustering, so palette entries go to the
+//! parts of the images that matter most.
+
+use std::io;
+
+use image_lib;
+use image_lib::{GrayImage, ImageError, Rgba, RgbaImage};
+
+use options::Settings;
+
+/// Automatic weights are fixed point, with a pixel's usual weight this
+/// much, so they can count for fractions of it.
+const AUTO_WEIGHT_SCALE: f64 = 16.0;
+
+/// The largest Sobel gradient magnitude of 8 bit intensities.
+const MAX_GRADIENT: f64 = 4.0 * 255.0 * ::std::f64::consts::SQRT_2;
+
+/// The weight of each pixel, in row order, from the masks and automatic
+/// weighting in the settings, or `None` if every pixel counts the same.
+/// With both, they're multiplied.
+pub fn pixel_weights(
+    images: &[RgbaImage],
+    settings: &Settings,
+) -> Result<Option<Vec<u32>>, ImageError> {
+    let mask_weights = read_mask_weights(images, settings)?;
+    if settings.edge_weight.is_none() && settings.flat_weight.is_none() {
+        return Ok(mask_weights);
+    }
+
+    let mut weights = auto_weights(
+        images,
+        settings.edge_weight.unwrap_or(0.0),
+        settings.flat_weight.unwrap_or(1.0),
+    );
+    if let Some(mask_weights) = mask_weights {
+        for (weight, mask_weight) in weights.iter_mut().zip(mask_weights) {
+            *weight *= mask_weight;
+        }
+    }
+    Ok(Some(weights))
+}
+
+fn read_mask_weights(
+    images: &[RgbaImage],
+    settings: &Settings,
+) -> Result<Option<Vec<u32>>, ImageError> {
+    if settings.masks.is_empty() {
+        return Ok(None);
+    }
+    if settings.masks.len() != 1 && settings.masks.len() != images.len() {
+        let message = format!(
+            "{} masks were given for {} images; give one for all of them or one for each.",
+            settings.masks.len(),
+            images.len()
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    let mut masks = Vec::with_capacity(settings.masks.len());
+    for path in &settings.masks {
+        let mask = image_lib::open(path)?.to_luma();
+        if mask.dimensions() != images[0].dimensions() {
+            let message = format!(
+                "The mask {} is {}x{}, but the images are {}x{}.",
+                path.display(),
+                mask.width(),
+                mask.height(),
+                images[0].width(),
+                images[0].height()
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
+        masks.push(mask);
+    }
+    Ok(Some(mask_weights(&masks)))
+}
+
+/// Combines the masks into a weight for each pixel, taking the brightest
+/// mask at each pixel so it counts fully if it matters in any image. Black
+/// still counts for a little, so every pixel has a color near it.
+pub fn mask_weights(masks: &[GrayImage]) -> Vec<u32> {
+    let mut weights = vec![1; masks[0].len()];
+    for mask in masks {
+        for (weight, &value) in weights.iter_mut().zip(mask.iter()) {
+            *weight = (*weight).max(u32::from(value));
+        }
+    }
+    weights
+}
+
+/// Weights each pixel by how strong an edge it's on, taking the strongest in
+/// any image, so thin outlines and small details get palette entries of
+/// their own instead of being averaged into the areas around them. Pixels in
+/// flat regions, a single color in every image, are scaled by `flat_weight`.
+pub fn auto_weights(images: &[RgbaImage], edge_weight: f64, flat_weight: f64) -> Vec<u32> {
+    let (width, height) = images[0].dimensions();
+    let mut edge_strength = vec![0.0f64; (width * height) as usize];
+    let mut flat = vec![true; (width * height) as usize];
+    for image in images {
+        let intensity: Vec<f64> = image.pixels().map(|pixel| intensity(*pixel)).collect();
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                let magnitude = sobel_magnitude(&intensity, width, height, x, y) / MAX_GRADIENT;
+                edge_strength[index] = edge_strength[index].max(magnitude);
+                flat[index] = flat[index] && is_flat(image, x, y);
+            }
+        }
+    }
+
+    edge_strength
+        .iter()
+        .zip(flat)
+        .map(|(&strength, flat)| {
+            let mut weight = 1.0 + edge_weight * strength;
+            if flat {
+                weight *= flat_weight;
+            }
+            ((weight * AUTO_WEIGHT_SCALE).round() as u32).max(1)
+        })
+        .collect()
+}
+
+/// Rec. 601 intensity, darkened by transparency so edges against
+/// transparent pixels count too.
+fn intensity(pixel: Rgba<u8>) -> f64 {
+    let [r, g, b, a] = pixel.data;
+    let luma = 0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b);
+    luma * f64::from(a) / 255.0
+}
+
+/// The magnitude of the Sobel gradient at a pixel, repeating the edge pixels
+/// past the borders.
+fn sobel_magnitude(intensity: &[f64], width: u32, height: u32, x: u32, y: u32) -> f64 {
+    let at = |dx: i32, dy: i32| {
+        let x = (x as i32 + dx).max(0).min(width as i32 - 1);
+        let y = (y as i32 + dy).max(0).min(height as i32 - 1);
+        intensity[(y as u32 * width + x as u32) as usize]
+    };
+    let horizontal =
+        at(1, -1) + 2.0 * at(1, 0) + at(1, 1) - at(-1, -1) - 2.0 * at(-1, 0) - at(-1, 1);
+    let vertical = at(-1, 1) + 2.0 * at(0, 1) + at(1, 1) - at(-1, -1) - 2.0 * at(0, -1) - at(1, -1);
+    horizontal.hypot(vertical)
+}
+
+/// Whether a pixel and all of its neighbors are the same color.
+fn is_flat(image: &RgbaImage, x: u32, y: u32) -> bool {
+    let color = image.get_pixel(x, y);
+    for ny in y.saturating_sub(1)..(y + 2).min(image.height()) {
+        for nx in x.saturating_sub(1)..(x + 2).min(image.width()) {
+            if image.get_pixel(nx, ny) != color {
+                return false;
+            }
+        }
+    }
+    true
+}
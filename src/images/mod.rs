@@ -1,9 +1,10 @@
 //! Handles quantization of images.
 
 use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use image_lib;
 use image_lib::{ImageError, Pixel as PixelTrait, RgbaImage};
@@ -11,66 +12,565 @@ use image_lib::{ImageError, Pixel as PixelTrait, RgbaImage};
 use png;
 use png::HasParameters;
 
-use color::combination::ConvertibleColorCombination;
-use color::{Color, Pixel, Rgb5a3, Rgba8};
-use k_means::Grouped;
-use options::ColorType;
+use color::combination::{ColorCombination, ConvertibleColorCombination, Metric};
+use color::{
+    Abgr1555, AlphaDistance, Argb1555, Bgr555, Color, ConvertibleColor, Custom, Ia4, Ia8,
+    LumaCoefficients, Master, MasterPalette, Pixel, Rec601, Rec709, Rgb565, Rgb5a3, Rgba4444,
+    Rgba8,
+};
+use k_means::{Grouped, Input, SimpleInput};
+use options::{AlphaMode, ColorType, ErrorTarget, Luma, RawLayout, Settings};
+use palette;
+
+use self::remap::{read_palette, remap_images};
+
+mod bundle;
+mod compose;
+mod heatmap;
+mod importance;
+mod raw;
+mod remap;
+mod report;
+mod source;
 
 #[cfg(test)]
 mod tests;
 
+/// Calls a function that's generic over the output color type, with the type
+/// chosen in the settings. What colors of that type are created with, such as
+/// a custom format, is passed as the last argument.
+macro_rules! with_color_type {
+    ($settings:expr, $function:ident($($argument:expr),*)) => {
+        match (&$settings.colortype, $settings.luma) {
+            (ColorType::Rgba8, _) => $function::<Rgba8>($($argument,)* ()),
+            (ColorType::Rgb5a3, _) => $function::<Rgb5a3>($($argument,)* ()),
+            (ColorType::Rgb565, _) => $function::<Rgb565>($($argument,)* ()),
+            (ColorType::Ia8, Luma::Rec601) => $function::<Ia8<Rec601>>($($argument,)* ()),
+            (ColorType::Ia8, Luma::Rec709) => $function::<Ia8<Rec709>>($($argument,)* ()),
+            (ColorType::Ia4, Luma::Rec601) => $function::<Ia4<Rec601>>($($argument,)* ()),
+            (ColorType::Ia4, Luma::Rec709) => $function::<Ia4<Rec709>>($($argument,)* ()),
+            (ColorType::Bgr555, _) => $function::<Bgr555>($($argument,)* ()),
+            (ColorType::Abgr1555, _) => $function::<Abgr1555>($($argument,)* ()),
+            (ColorType::Rgba4444, _) => $function::<Rgba4444>($($argument,)* ()),
+            (ColorType::Argb1555, _) => $function::<Argb1555>($($argument,)* ()),
+            (ColorType::Master(palette), _) => $function::<Master>($($argument,)* palette),
+            (ColorType::Custom(format), Luma::Rec601) => {
+                $function::<Custom<Rec601>>($($argument,)* format)
+            }
+            (ColorType::Custom(format), Luma::Rec709) => {
+                $function::<Custom<Rec709>>($($argument,)* format)
+            }
+        }
+    };
+}
+
+/// Fully transparent, the color palette index 0 holds when it's reserved.
+const TRANSPARENT: Pixel = Pixel { data: [0, 0, 0, 0] };
+
 /// Quantize a set of input images, and writes the output.
 pub fn quantize<'a, 'b, I, O>(
     input_paths: I,
     output_paths: O,
-    colortype: ColorType,
-    num_colors: u32,
-    verbose: bool,
+    settings: &Settings,
 ) -> Result<(), ImageError>
 where
     I: Iterator<Item = &'a Path>,
     O: Iterator<Item = &'b Path>,
 {
-    let images = try!(open_images(input_paths));
+    let input_paths: Vec<&Path> = input_paths.collect();
+    let output_paths: Vec<&Path> = output_paths.collect();
+    let originals = try!(open_images(input_paths.iter().cloned()));
 
-    let quantization_map =
-        quantization_map_from_images_and_color_type(&images, colortype, num_colors, verbose);
+    if !settings.weights.is_empty() && settings.weights.len() != originals.len() {
+        let message = format!(
+            "{} weights were given for {} images.",
+            settings.weights.len(),
+            originals.len()
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+    let metric = metric(settings);
+
+    if let Some(colors) = settings
+        .locked
+        .iter()
+        .find(|colors| colors.len() != 1 && colors.len() != originals.len())
+    {
+        let message = format!(
+            "A locked color combination has {} colors, but there are {} images.",
+            colors.len(),
+            originals.len()
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    let images = prepare_images(&originals, &input_paths, settings)?;
+    let reserves_transparent_index = reserves_transparent_index(&images, settings);
+
+    let available_colors = if reserves_transparent_index {
+        settings.num_colors.saturating_sub(1)
+    } else {
+        settings.num_colors
+    };
+    if settings.locked.len() > available_colors as usize {
+        let message = format!(
+            "{} colors are locked, but there's only room for {}.",
+            settings.locked.len(),
+            available_colors
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    if !settings.remap.is_empty() {
+        let (color_combinations, indexed_image_data) =
+            with_color_type!(settings, remap_images(&images, &metric, settings))?;
+        return write_output(
+            &originals,
+            &input_paths,
+            &output_paths,
+            color_combinations.iter().collect(),
+            indexed_image_data,
+            settings,
+        );
+    }
+
+    let pixel_weights = importance::pixel_weights(&images, settings)?;
+    let quantization_map = quantization_map_from_images_and_color_type(
+        &images,
+        pixel_weights.as_deref(),
+        &metric,
+        settings,
+    );
 
     let mut color_combinations = ::std::collections::HashSet::new();
     for color_combination in quantization_map.values() {
         color_combinations.insert(color_combination);
     }
 
-    if verbose {
+    if settings.verbose {
         println!(
             "{} color combinations in output images",
             color_combinations.len()
         );
     }
 
-    let ordered_color_combinations = order_color_combinations(color_combinations);
+    let mut ordered_color_combinations = order_color_combinations(color_combinations);
+    let transparent_combination = vec![TRANSPARENT; images.len()];
+    if reserves_transparent_index {
+        ordered_color_combinations.retain(|&combination| *combination != transparent_combination);
+        ordered_color_combinations.insert(0, &transparent_combination);
+    }
 
     let indexed_quantization_map =
         index_quantization_map(&quantization_map, &ordered_color_combinations);
+    let indexed_image_data = calculate_indexes(&images, indexed_quantization_map);
 
-    let width = images[0].width();
-    let height = images[0].height();
+    write_output(
+        &originals,
+        &input_paths,
+        &output_paths,
+        ordered_color_combinations,
+        indexed_image_data,
+        settings,
+    )
+}
 
-    let indexed_image_data = calculate_indexes(images, indexed_quantization_map);
+/// Writes the output images with their palettes, along with any reports and
+/// extra files the settings ask for. Reports and heatmaps compare against the
+/// original images, before `prepare_images` changed them.
+fn write_output(
+    originals: &[RgbaImage],
+    input_paths: &[&Path],
+    output_paths: &[&Path],
+    ordered_color_combinations: Vec<&Vec<Pixel>>,
+    indexed_image_data: Vec<u8>,
+    settings: &Settings,
+) -> Result<(), ImageError> {
+    if let ColorType::Master(ref palette) = settings.colortype {
+        print_master_palette_use(palette, input_paths, &ordered_color_combinations);
+    }
+
+    let width = originals[0].width();
+    let height = originals[0].height();
+
+    if let Some(ref layout) = settings.raw {
+        if layout.bits_per_index == 4 && ordered_color_combinations.len() > 16 {
+            let message = format!(
+                "{} colors can't be stored in 4 bit indexes.",
+                ordered_color_combinations.len()
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
+        let native_palettes =
+            with_color_type!(settings, native_palettes(&ordered_color_combinations));
+        for (output_path, native_palette) in output_paths.iter().zip(native_palettes.iter()) {
+            let data = raw::palette_data(native_palette, ordered_color_combinations.len(), layout);
+            fs::write(derived_path(output_path, "palette", "bin"), data)?;
+        }
+        let data = raw::index_data(&indexed_image_data, width, height, layout)?;
+        fs::write(derived_path(output_paths[0], "indexes", "bin"), data)?;
+    } else if settings.raw_palette {
+        let native_palettes =
+            with_color_type!(settings, native_palettes(&ordered_color_combinations));
+        for (output_path, native_palette) in output_paths.iter().zip(native_palettes.iter()) {
+            fs::write(derived_path(output_path, "palette", "bin"), native_palette)?;
+        }
+    }
+    if !settings.source_languages.is_empty() {
+        let layout = RawLayout {
+            header: false,
+            ..settings.raw.unwrap_or_default()
+        };
+        let indexes = raw::index_data(&indexed_image_data, width, height, &layout)?;
+        let (value_size, palettes) =
+            with_color_type!(settings, native_palette_values(&ordered_color_combinations));
+        let data = source::SourceData {
+            indexes: &indexes,
+            width,
+            height,
+            palettes: &palettes,
+            value_size,
+        };
+        for &language in &settings.source_languages {
+            let code = source::source_code(
+                language,
+                &data,
+                &settings.symbol_prefix,
+                settings.values_per_line,
+            );
+            fs::write(output_paths[0].with_extension(language.extension()), code)?;
+        }
+    }
+    let palettes = image_palettes(&ordered_color_combinations);
+    for format in &settings.palette_formats {
+        for (output_path, colors) in output_paths.iter().zip(palettes.iter()) {
+            let path = derived_path(output_path, "palette", format.extension());
+            palette::write(&path, *format, colors)?;
+        }
+    }
+    if let Some((ref path, format)) = settings.combined_palette {
+        palette::write(path, format, &palettes.concat())?;
+    }
     let (rgb_palettes, alpha_palettes) = calculate_palettes(ordered_color_combinations);
 
-    try!(write_pngs(
-        output_paths,
-        indexed_image_data,
-        rgb_palettes,
-        alpha_palettes,
-        width,
-        height
-    ));
+    let wants_report = settings.report || settings.report_json.is_some();
+    if wants_report || settings.heatmap || settings.composite {
+        let alpha_distance = alpha_distance(settings);
+        let quantized_images = reconstruct_images(
+            &indexed_image_data,
+            &rgb_palettes,
+            &alpha_palettes,
+            width,
+            height,
+        );
+
+        if wants_report {
+            let reports: Vec<_> = input_paths
+                .iter()
+                .zip(originals.iter().zip(quantized_images.iter()))
+                .map(|(path, (original, quantized))| {
+                    report::image_report(
+                        &path.display().to_string(),
+                        original,
+                        quantized,
+                        &indexed_image_data,
+                        alpha_distance,
+                    )
+                })
+                .collect();
+
+            if settings.report {
+                report::print_reports(&reports);
+            }
+            if let Some(ref report_path) = settings.report_json {
+                report::write_json_reports(report_path, &reports)?;
+            }
+        }
+
+        if settings.heatmap || settings.composite {
+            let max_error = heatmap::max_error(originals, &quantized_images, alpha_distance);
+            for ((output_path, original), quantized) in output_paths
+                .iter()
+                .zip(originals.iter())
+                .zip(quantized_images.iter())
+            {
+                let heatmap = heatmap::heatmap(original, quantized, max_error, alpha_distance);
+                if settings.composite {
+                    heatmap::composite(original, quantized, &heatmap)?.save(derived_path(
+                        output_path,
+                        "composite",
+                        "png",
+                    ))?;
+                }
+                if settings.heatmap {
+                    heatmap.save(derived_path(output_path, "heatmap", "png"))?;
+                }
+            }
+        }
+    }
+
+    if settings.bundle {
+        bundle::write_bundle(
+            input_paths,
+            output_paths,
+            &indexed_image_data,
+            &palettes,
+            width,
+            height,
+            settings,
+        )?;
+    } else if settings.raw.is_none() {
+        write_pngs(
+            output_paths.iter().cloned(),
+            indexed_image_data,
+            rgb_palettes,
+            alpha_palettes,
+            width,
+            height,
+        )?;
+    }
 
     Ok(())
 }
 
+/// Copies of the images ready to quantize: applies the alpha mode, handles
+/// transparency for the reserved index, and converts them to grayscale for
+/// the grayscale color types.
+fn prepare_images(
+    images: &[RgbaImage],
+    input_paths: &[&Path],
+    settings: &Settings,
+) -> Result<Vec<RgbaImage>, ImageError> {
+    let mut images = images.to_vec();
+    match settings.alpha {
+        AlphaMode::Matte(matte) => {
+            for image in images.iter_mut() {
+                flatten(image, matte);
+            }
+        }
+        AlphaMode::Threshold => {
+            for image in images.iter_mut() {
+                threshold_alpha(image);
+            }
+        }
+        AlphaMode::Keep | AlphaMode::Premultiplied => {}
+    }
+    if !settings.colortype.has_alpha() && !settings.reserves_transparent_index() {
+        for (path, image) in input_paths.iter().zip(images.iter()) {
+            if image.pixels().any(|pixel| pixel[3] != 0xFF) {
+                let message = format!(
+                    "{} has transparent pixels, which {} can't store; \
+                     use --matte to flatten them against a color.",
+                    path.display(),
+                    settings.colortype.name()
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+            }
+        }
+    }
+
+    if settings.reserves_transparent_index() {
+        if settings.colortype.has_alpha() {
+            for image in images.iter_mut() {
+                clear_transparent(image);
+            }
+        } else {
+            for image in images.iter_mut() {
+                threshold_alpha(image);
+            }
+            check_transparency_matches(&images, &settings.colortype)?;
+        }
+    }
+
+    if settings.colortype.is_grayscale() {
+        for image in images.iter_mut() {
+            match settings.luma {
+                Luma::Rec601 => convert_to_grayscale::<Rec601>(image),
+                Luma::Rec709 => convert_to_grayscale::<Rec709>(image),
+            }
+        }
+    }
+
+    Ok(images)
+}
+
+/// Whether palette index 0 holds full transparency for these images. The
+/// color types that always reserve it do; otherwise it's only taken when asked
+/// for and some pixel is transparent in every image, so it isn't wasted.
+fn reserves_transparent_index(images: &[RgbaImage], settings: &Settings) -> bool {
+    if settings.colortype.reserves_transparent_index() {
+        return true;
+    }
+    settings.transparent_index && {
+        let (width, height) = images[0].dimensions();
+        (0..height)
+            .any(|y| (0..width).any(|x| images.iter().all(|image| image.get_pixel(x, y)[3] == 0)))
+    }
+}
+
+/// How color combinations are compared under the settings.
+fn metric(settings: &Settings) -> Metric {
+    Metric {
+        image_weights: if settings.weights.is_empty() {
+            None
+        } else {
+            Some(settings.weights.clone())
+        },
+        alpha: alpha_distance(settings),
+    }
+}
+
+/// How colors are compared under the alpha settings.
+fn alpha_distance(settings: &Settings) -> AlphaDistance {
+    AlphaDistance {
+        weight: settings.alpha_weight,
+        premultiplied: settings.alpha == AlphaMode::Premultiplied,
+    }
+}
+
+/// Rebuilds a full color image for each palette from the indexes, writing
+/// them to the output paths. Raw palettes are read in the color type from the
+/// settings.
+pub fn compose<'a, 'b, P, O>(
+    index_path: &Path,
+    palette_paths: P,
+    output_paths: O,
+    settings: &Settings,
+) -> Result<(), ImageError>
+where
+    P: Iterator<Item = &'a Path>,
+    O: Iterator<Item = &'b Path>,
+{
+    let (indexes, width, height) = compose::read_indexes(index_path)?;
+    for (palette_path, output_path) in palette_paths.zip(output_paths) {
+        let palette = with_color_type!(settings, read_palette(palette_path))?;
+        let image =
+            compose::compose_image(&indexes, width, height, &palette).map_err(|message| {
+                let message = format!("{}: {}", palette_path.display(), message);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            })?;
+        image.save(output_path)?;
+    }
+    Ok(())
+}
+
+/// Finds a palette for each new image that fits indexes already in use, so
+/// the images can be added to a set without changing its indexes, and
+/// writes the output like `quantize`.
+pub fn add_to_indexes<'a, 'b, I, O>(
+    index_path: &Path,
+    input_paths: I,
+    output_paths: O,
+    settings: &Settings,
+) -> Result<(), ImageError>
+where
+    I: Iterator<Item = &'a Path>,
+    O: Iterator<Item = &'b Path>,
+{
+    let input_paths: Vec<&Path> = input_paths.collect();
+    let output_paths: Vec<&Path> = output_paths.collect();
+    let (indexes, width, height) = compose::read_indexes(index_path)?;
+    let originals = open_images(input_paths.iter().cloned())?;
+    for (path, image) in input_paths.iter().zip(originals.iter()) {
+        if image.dimensions() != (width, height) {
+            let message = format!(
+                "{} is {}x{}, but the indexes are for {}x{} images.",
+                path.display(),
+                image.width(),
+                image.height(),
+                width,
+                height
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
+    }
+
+    let images = prepare_images(&originals, &input_paths, settings)?;
+
+    let pixel_weights = importance::pixel_weights(&images, settings)?;
+    let color_combinations = with_color_type!(
+        settings,
+        palettes_for_indexes(&images, &indexes, pixel_weights.as_deref(), settings)
+    );
+    write_output(
+        &originals,
+        &input_paths,
+        &output_paths,
+        color_combinations.iter().collect(),
+        indexes,
+        settings,
+    )
+}
+
+/// The palette entries that best fit the images to the indexes. Each entry is
+/// the mean of the pixels with that index, weighted as k-means weights them.
+fn palettes_for_indexes<O: Color>(
+    images: &[RgbaImage],
+    indexes: &[u8],
+    pixel_weights: Option<&[u32]>,
+    settings: &Settings,
+    context: O::Context,
+) -> Vec<Vec<Pixel>> {
+    let num_entries = indexes.iter().max().map_or(0, |&index| index as usize + 1);
+    let mut color_combinations = vec![Vec::with_capacity(images.len()); num_entries];
+    for image in images {
+        let mut colors_per_index = vec![Vec::new(); num_entries];
+        for (pixel_index, (&pixel, &index)) in image.pixels().zip(indexes.iter()).enumerate() {
+            let weight = pixel_weights.map_or(1, |weights| u64::from(weights[pixel_index]));
+            colors_per_index[index as usize]
+                .push((ConvertibleColor::<Rgba8, O>::new(pixel, context), weight));
+        }
+
+        for (color_combination, colors) in color_combinations.iter_mut().zip(colors_per_index) {
+            let grouped_colors = ::k_means::collect_weighted_groups(colors.into_iter());
+            // Unused indexes keep a placeholder, so the others don't move.
+            let color = if grouped_colors.is_empty() {
+                TRANSPARENT
+            } else {
+                let grouped_colors: Vec<_> = grouped_colors.iter().collect();
+                Input::mean_of(&grouped_colors).as_pixel()
+            };
+            color_combination.push(color);
+        }
+    }
+
+    if reserves_transparent_index(images, settings) && num_entries > 0 {
+        color_combinations[0] = vec![TRANSPARENT; images.len()];
+    }
+    color_combinations
+}
+
+/// Lists which colors of the master palette each image ended up using.
+fn print_master_palette_use(
+    palette: &MasterPalette,
+    input_paths: &[&Path],
+    color_combinations: &[&Vec<Pixel>],
+) {
+    for (image_index, path) in input_paths.iter().enumerate() {
+        let mut codes: Vec<u32> = color_combinations
+            .iter()
+            .filter_map(|color_combination| palette.code_of(color_combination[image_index]))
+            .collect();
+        codes.sort();
+        codes.dedup();
+        let codes: Vec<String> = codes.iter().map(|code| format!("{:02X}", code)).collect();
+        println!(
+            "{}: {} of {} {} colors: {}",
+            path.display(),
+            codes.len(),
+            palette.len(),
+            palette.name(),
+            codes.join(" ")
+        );
+    }
+}
+
+/// A path for another file to go with an output image, such as
+/// "image (smashed).heatmap.png" for "image (smashed).png".
+fn derived_path(output_path: &Path, kind: &str, extension: &str) -> PathBuf {
+    output_path.with_extension(format!("{}.{}", kind, extension))
+}
+
 fn open_images<'a, I: Iterator<Item = &'a Path>>(
     input_paths: I,
 ) -> Result<Vec<RgbaImage>, ImageError> {
@@ -82,39 +582,149 @@ fn open_images<'a, I: Iterator<Item = &'a Path>>(
     Ok(images)
 }
 
+/// Composites an image over a solid color, making it opaque.
+fn flatten(image: &mut RgbaImage, matte: Pixel) {
+    for pixel in image.pixels_mut() {
+        let alpha = u32::from(pixel[3]);
+        for channel in 0..3 {
+            let blended =
+                u32::from(pixel[channel]) * alpha + u32::from(matte[channel]) * (255 - alpha);
+            pixel[channel] = ((blended + 127) / 255) as u8;
+        }
+        pixel[3] = 0xFF;
+    }
+}
+
+/// Replaces each color with its intensity, so the grayscale color types
+/// cluster on intensity alone.
+fn convert_to_grayscale<L: LumaCoefficients>(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        *pixel = Ia8::<L>::new(Rgba8::from(*pixel).components(), ()).as_pixel();
+    }
+}
+
+/// Makes every pixel either fully transparent or opaque, for 1-bit
+/// transparency and the color types that can only show transparency with a
+/// reserved index.
+fn threshold_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        if pixel[3] < 0x80 {
+            *pixel = TRANSPARENT;
+        } else {
+            pixel[3] = 0xFF;
+        }
+    }
+}
+
+/// Makes every fully transparent pixel the same color, so they all share the
+/// reserved index.
+fn clear_transparent(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        if pixel[3] == 0 {
+            *pixel = TRANSPARENT;
+        }
+    }
+}
+
+/// Checks that each pixel is either transparent in every image or in none,
+/// since the reserved transparent index is shared by all of the palettes.
+fn check_transparency_matches(images: &[RgbaImage], colortype: &ColorType) -> io::Result<()> {
+    for (x, y, pixel) in images[0].enumerate_pixels() {
+        let transparent = pixel[3] == 0;
+        if images[1..]
+            .iter()
+            .any(|image| (image.get_pixel(x, y)[3] == 0) != transparent)
+        {
+            let message = format!(
+                "Pixel ({}, {}) is transparent in some images but not others, which {} \
+                 can't store since transparency is always palette index 0.",
+                x,
+                y,
+                colortype.name()
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+    }
+    Ok(())
+}
+
 fn quantization_map_from_images_and_color_type(
     images: &[RgbaImage],
-    colortype: ColorType,
-    num_colors: u32,
-    verbose: bool,
+    pixel_weights: Option<&[u32]>,
+    metric: &Metric,
+    settings: &Settings,
 ) -> HashMap<Vec<Pixel>, Vec<Pixel>> {
-    match colortype {
-        ColorType::Rgba8 => quantization_map_from_images::<Rgba8>(images, num_colors, verbose),
-        ColorType::Rgb5a3 => quantization_map_from_images::<Rgb5a3>(images, num_colors, verbose),
-    }
+    with_color_type!(
+        settings,
+        quantization_map_from_images(images, pixel_weights, metric, settings)
+    )
 }
 
 fn quantization_map_from_images<O: Color>(
     images: &[RgbaImage],
-    num_colors: u32,
-    verbose: bool,
+    pixel_weights: Option<&[u32]>,
+    metric: &Metric,
+    settings: &Settings,
+    context: O::Context,
 ) -> HashMap<Vec<Pixel>, Vec<Pixel>> {
-    let color_combinations = get_color_combinations::<O>(images);
-    let grouped_color_combinations = group_color_combinations(color_combinations);
+    let color_combinations = get_color_combinations::<O>(images, metric, context);
+    let mut grouped_color_combinations =
+        group_color_combinations(color_combinations, pixel_weights);
 
-    if verbose {
+    if settings.verbose {
         println!(
             "{} color combinations in input images",
             grouped_color_combinations.len()
         );
     }
 
-    quantization_map_from_items(grouped_color_combinations, num_colors, verbose)
+    let num_colors = match &settings.colortype {
+        // There's no use asking for more combinations than the master palette
+        // can make; the extra centers could only be duplicates.
+        ColorType::Master(palette) => {
+            let combinations = (palette.len() as u32).saturating_pow(images.len() as u32);
+            settings.num_colors.min(combinations)
+        }
+        _ => settings.num_colors,
+    };
+
+    if !reserves_transparent_index(images, settings) {
+        return quantization_map_from_items(
+            grouped_color_combinations,
+            images.len(),
+            num_colors,
+            metric,
+            settings,
+            context,
+        );
+    }
+
+    // Transparent pixels all go to the reserved index, leaving the rest of
+    // the palette for the opaque ones.
+    let transparent_combination = vec![TRANSPARENT; images.len()];
+    grouped_color_combinations
+        .retain(|grouped| grouped.data.as_pixels() != transparent_combination);
+    let mut quantization_map = if grouped_color_combinations.is_empty() {
+        HashMap::new()
+    } else {
+        quantization_map_from_items(
+            grouped_color_combinations,
+            images.len(),
+            num_colors - 1,
+            metric,
+            settings,
+            context,
+        )
+    };
+    quantization_map.insert(transparent_combination.clone(), transparent_combination);
+    quantization_map
 }
 
-fn get_color_combinations<O: Color>(
+fn get_color_combinations<'m, O: Color>(
     images: &[RgbaImage],
-) -> Vec<ConvertibleColorCombination<Rgba8, O>> {
+    metric: &'m Metric,
+    context: O::Context,
+) -> Vec<ConvertibleColorCombination<'m, Rgba8, O>> {
     let width = images[0].width();
     let height = images[0].height();
 
@@ -124,8 +734,9 @@ fn get_color_combinations<O: Color>(
             let color_combination = ConvertibleColorCombination::<Rgba8, O>::new(
                 images
                     .iter()
-                    .map(|image| (*image.get_pixel(x, y)).into())
+                    .map(|image| ConvertibleColor::new(*image.get_pixel(x, y), context))
                     .collect(),
+                metric,
             );
             color_combinations.push(color_combination);
         }
@@ -134,19 +745,127 @@ fn get_color_combinations<O: Color>(
     color_combinations
 }
 
-fn group_color_combinations<O: Color>(
-    color_combinations: Vec<ConvertibleColorCombination<Rgba8, O>>,
-) -> Vec<Grouped<ConvertibleColorCombination<Rgba8, O>>> {
-    ::k_means::collect_groups(color_combinations.into_iter())
+/// Groups identical color combinations, with each counting its pixel's
+/// weight if there are weights.
+fn group_color_combinations<'m, O: Color>(
+    color_combinations: Vec<ConvertibleColorCombination<'m, Rgba8, O>>,
+    pixel_weights: Option<&[u32]>,
+) -> Vec<Grouped<ConvertibleColorCombination<'m, Rgba8, O>>> {
+    match pixel_weights {
+        Some(weights) => ::k_means::collect_weighted_groups(
+            color_combinations
+                .into_iter()
+                .zip(weights.iter().map(|&weight| u64::from(weight))),
+        ),
+        None => ::k_means::collect_groups(color_combinations.into_iter()),
+    }
 }
 
-fn quantization_map_from_items<O: Color>(
-    grouped_color_combinations: Vec<Grouped<ConvertibleColorCombination<Rgba8, O>>>,
+/// The locked color combinations as cluster centers, with a single color
+/// standing for the same color in every image.
+fn locked_centers<'m, O: Color>(
+    locked: &[Vec<Pixel>],
+    num_images: usize,
+    metric: &'m Metric,
+    context: O::Context,
+) -> Vec<ColorCombination<'m, O>> {
+    let mut centers = Vec::with_capacity(locked.len());
+    for colors in locked {
+        let center = ColorCombination::new(
+            (0..num_images)
+                .map(|image_index| {
+                    let pixel = if colors.len() == 1 {
+                        colors[0]
+                    } else {
+                        colors[image_index]
+                    };
+                    O::new(Rgba8::from(pixel).components(), context)
+                })
+                .collect(),
+            metric,
+        );
+        if !centers.contains(&center) {
+            centers.push(center);
+        }
+    }
+    centers
+}
+
+fn quantization_map_from_items<'m, O: Color>(
+    grouped_color_combinations: Vec<Grouped<ConvertibleColorCombination<'m, Rgba8, O>>>,
+    num_images: usize,
     num_colors: u32,
-    verbose: bool,
+    metric: &'m Metric,
+    settings: &Settings,
+    context: O::Context,
 ) -> HashMap<Vec<Pixel>, Vec<Pixel>> {
-    let (centers, grouped_color_combinations_per_cluster) =
-        ::k_means::run(&grouped_color_combinations, num_colors, verbose);
+    let locked_centers = locked_centers::<O>(&settings.locked, num_images, metric, context);
+    let total_weight = if settings.weights.is_empty() {
+        num_images as f64
+    } else {
+        settings.weights.iter().sum()
+    };
+    let cluster = |fixed_centers: &[ColorCombination<'m, O>]| {
+        let (centers, grouped_color_combinations_per_cluster) = match settings.error_target {
+            Some(target) => ::k_means::run_to_target(
+                &grouped_color_combinations,
+                num_colors,
+                fixed_centers,
+                |centers, grouped_color_combinations_per_cluster| {
+                    meets_error_target(
+                        target,
+                        centers,
+                        grouped_color_combinations_per_cluster,
+                        total_weight,
+                    )
+                },
+                settings.restarts,
+                settings.verbose,
+            ),
+            None => ::k_means::run_with_restarts(
+                &grouped_color_combinations,
+                num_colors,
+                fixed_centers,
+                settings.restarts,
+                settings.verbose,
+            ),
+        };
+        if settings.refine_moves > 0 {
+            ::k_means::refine(
+                &grouped_color_combinations,
+                centers,
+                grouped_color_combinations_per_cluster,
+                fixed_centers.len(),
+                settings.refine_moves,
+                settings.verbose,
+            )
+        } else {
+            (centers, grouped_color_combinations_per_cluster)
+        }
+    };
+    let (mut centers, mut grouped_color_combinations_per_cluster) = cluster(&locked_centers);
+
+    // Colors far from every center get entries of their own, locked like the
+    // locked colors, while there's room for them.
+    if let Some(threshold) = settings.outlier_threshold {
+        let (protected_centers, protected_clusters, num_fixed) = ::k_means::protect_outliers(
+            &grouped_color_combinations,
+            centers,
+            grouped_color_combinations_per_cluster,
+            locked_centers.len(),
+            num_colors as usize,
+            threshold,
+            |grouped, center| grouped.data.max_distance_to(center),
+        );
+        if settings.verbose {
+            println!(
+                "Protected {} outlying color combinations",
+                num_fixed - locked_centers.len()
+            );
+        }
+        centers = protected_centers;
+        grouped_color_combinations_per_cluster = protected_clusters;
+    }
 
     let mut quantization_map = HashMap::new();
     for (center, grouped_color_combinations) in centers
@@ -161,9 +880,76 @@ fn quantization_map_from_items<O: Color>(
         }
     }
 
+    // Locked colors stay in the palette even if no pixel ends up using them.
+    for center in &locked_centers {
+        let pixels = center.as_pixels();
+        quantization_map.entry(pixels.clone()).or_insert(pixels);
+    }
+
     quantization_map
 }
 
+type Clusters<'a, 'm, O> = [Vec<&'a Grouped<ConvertibleColorCombination<'m, Rgba8, O>>>];
+
+/// Whether the clusters' error per pixel of each image is within the target.
+/// With image weights, the mean error is a weighted mean over the images.
+fn meets_error_target<O: Color>(
+    target: ErrorTarget,
+    centers: &[ColorCombination<O>],
+    clusters: &Clusters<O>,
+    total_weight: f64,
+) -> bool {
+    match target {
+        ErrorTarget::Mean(max_mean_error) => {
+            mean_error(centers, clusters, total_weight) <= max_mean_error
+        }
+        ErrorTarget::Max(max_error) => max_error_of(centers, clusters) <= max_error,
+        ErrorTarget::Psnr(min_psnr) => {
+            psnr(mean_error(centers, clusters, total_weight)) >= min_psnr
+        }
+    }
+}
+
+/// The mean error per pixel of each image, where the total weight is the
+/// number of images when they aren't weighted.
+fn mean_error<O: Color>(
+    centers: &[ColorCombination<O>],
+    clusters: &Clusters<O>,
+    total_weight: f64,
+) -> f64 {
+    let mut total_distance = 0.0;
+    let mut total_count = 0.0;
+    for (center, grouped_color_combinations) in centers.iter().zip(clusters.iter()) {
+        for grouped in grouped_color_combinations {
+            let count = grouped.count as f64;
+            total_distance += grouped.data.distance_to(center) * count;
+            total_count += count;
+        }
+    }
+    total_distance / (total_count * total_weight)
+}
+
+fn max_error_of<O: Color>(centers: &[ColorCombination<O>], clusters: &Clusters<O>) -> f64 {
+    centers
+        .iter()
+        .zip(clusters.iter())
+        .flat_map(|(center, grouped_color_combinations)| {
+            grouped_color_combinations
+                .iter()
+                .map(move |grouped| grouped.data.max_distance_to(center))
+        })
+        .fold(0.0, f64::max)
+}
+
+/// The peak signal-to-noise ratio for a mean error, in decibels.
+///
+/// For opaque colors the distance is the sum of the squared differences of
+/// the three color components, so the mean squared error per component is a
+/// third of it.
+fn psnr(mean_error: f64) -> f64 {
+    10.0 * (3.0 / mean_error).log10()
+}
+
 fn order_color_combinations(color_combinations: HashSet<&Vec<Pixel>>) -> Vec<&Vec<Pixel>> {
     let mut ordered_color_combinations: Vec<&Vec<Pixel>> = color_combinations.into_iter().collect();
     ordered_color_combinations.sort_by_key(|color_combination| {
@@ -221,7 +1007,7 @@ fn index_quantization_map<'a, 'b>(
 }
 
 fn calculate_indexes(
-    images: Vec<RgbaImage>,
+    images: &[RgbaImage],
     quantization_map: HashMap<&Vec<Pixel>, usize>,
 ) -> Vec<u8> {
     let width = images[0].width();
@@ -266,6 +1052,90 @@ fn calculate_palettes(color_combinations: Vec<&Vec<Pixel>>) -> (Vec<Vec<u8>>, Ve
     (rgb_palettes, alpha_palettes)
 }
 
+/// Splits the palette of color combinations into each image's palette.
+fn image_palettes(color_combinations: &[&Vec<Pixel>]) -> Vec<Vec<Pixel>> {
+    let num_images = color_combinations[0].len();
+    (0..num_images)
+        .map(|image_index| {
+            color_combinations
+                .iter()
+                .map(|color_combination| color_combination[image_index])
+                .collect()
+        })
+        .collect()
+}
+
+/// Encodes each image's palette in the color type's native format.
+fn native_palettes<O: Color>(
+    color_combinations: &[&Vec<Pixel>],
+    context: O::Context,
+) -> Vec<Vec<u8>> {
+    let num_images = color_combinations[0].len();
+    (0..num_images)
+        .map(|image_index| {
+            color_combinations
+                .iter()
+                .flat_map(|color_combination| {
+                    let pixel = color_combination[image_index];
+                    O::new(Rgba8::from(pixel).components(), context).to_native()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Each image's palette in the color type's native format as numbers, along
+/// with the size of each number in bytes.
+fn native_palette_values<O: Color>(
+    color_combinations: &[&Vec<Pixel>],
+    context: O::Context,
+) -> (usize, Vec<Vec<u32>>) {
+    let size = O::native_size(context);
+    let little_endian = O::native_is_little_endian(context);
+    let palettes = native_palettes::<O>(color_combinations, context)
+        .iter()
+        .map(|native_palette| {
+            native_palette
+                .chunks(size)
+                .map(|bytes| {
+                    let combine = |value, &byte| value << 8 | u32::from(byte);
+                    if little_endian {
+                        bytes.iter().rev().fold(0, combine)
+                    } else {
+                        bytes.iter().fold(0, combine)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    (size, palettes)
+}
+
+/// Rebuilds the full color images from the indexes and palettes.
+fn reconstruct_images(
+    indexed_image_data: &[u8],
+    rgb_palettes: &[Vec<u8>],
+    alpha_palettes: &[Vec<u8>],
+    width: u32,
+    height: u32,
+) -> Vec<RgbaImage> {
+    rgb_palettes
+        .iter()
+        .zip(alpha_palettes.iter())
+        .map(|(rgb_palette, alpha_palette)| {
+            let mut image = RgbaImage::new(width, height);
+            for (pixel, &index) in image.pixels_mut().zip(indexed_image_data.iter()) {
+                let index = index as usize;
+                let rgb = &rgb_palette[index * 3..index * 3 + 3];
+                *pixel = Pixel {
+                    data: [rgb[0], rgb[1], rgb[2], alpha_palette[index]],
+                };
+            }
+            image
+        })
+        .collect()
+}
+
 fn write_pngs<'a, O>(
     output_paths: O,
     indexed_image_data: Vec<u8>,
@@ -0,0 +1,144 @@
+//! Writes indexes and palettes as raw binary files, for engines that load
+//! them directly instead of PNGs.
+//!
+//! With a header, the index file starts with 12 bytes: "CSIX", the width,
+//! height and bytes per row (including padding) as 16 bit numbers, the bits
+//! per index, and 1 if the first pixel of each byte is in the low nibble or
+//! 0 if not. Each palette file starts with 8 bytes: "CSPL", the number of
+//! colors as a 16 bit number, the bytes per color, and a 0. Numbers in the
+//! headers are little-endian.
+
+use std::io;
+
+use options::RawLayout;
+
+const INDEX_MAGIC: &[u8; 4] = b"CSIX";
+const PALETTE_MAGIC: &[u8; 4] = b"CSPL";
+
+/// The bytes in each row of indexes, including padding.
+fn row_stride(width: u32, layout: &RawLayout) -> usize {
+    let alignment = layout.row_alignment as usize;
+    let bytes = row_bytes(width, layout.bits_per_index);
+    (bytes + alignment - 1) / alignment * alignment
+}
+
+/// The bytes in each row of indexes, without padding.
+fn row_bytes(width: u32, bits_per_index: u8) -> usize {
+    (width as usize * bits_per_index as usize + 7) / 8
+}
+
+/// Lays out the indexes, packing two to a byte for 4 bits per index, and
+/// padding each row with zeros to the row alignment. Fails if the header
+/// can't hold the width, height or bytes per row.
+pub fn index_data(
+    indexes: &[u8],
+    width: u32,
+    height: u32,
+    layout: &RawLayout,
+) -> io::Result<Vec<u8>> {
+    let stride = row_stride(width, layout);
+    let mut data = Vec::with_capacity(header_size(layout, 12) + stride * height as usize);
+    if layout.header {
+        data.extend_from_slice(INDEX_MAGIC);
+        data.extend_from_slice(&header_number(width as usize, "width")?.to_le_bytes());
+        data.extend_from_slice(&header_number(height as usize, "height")?.to_le_bytes());
+        data.extend_from_slice(&header_number(stride, "row")?.to_le_bytes());
+        data.push(layout.bits_per_index);
+        data.push(layout.low_nibble_first as u8);
+    }
+
+    for row in indexes.chunks(width as usize) {
+        let row_start = data.len();
+        if layout.bits_per_index == 4 {
+            for pair in row.chunks(2) {
+                let first = pair[0];
+                let second = pair.get(1).cloned().unwrap_or(0);
+                data.push(if layout.low_nibble_first {
+                    second << 4 | first
+                } else {
+                    first << 4 | second
+                });
+            }
+        } else {
+            data.extend_from_slice(row);
+        }
+        data.resize(row_start + stride, 0);
+    }
+    Ok(data)
+}
+
+/// A number for a header, which only has 16 bits for it.
+fn header_number(number: usize, name: &str) -> io::Result<u16> {
+    if number > usize::from(u16::MAX) {
+        let message = format!(
+            "The {} of {} is too large for a raw header, which allows at most {}.",
+            name,
+            number,
+            u16::MAX
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    Ok(number as u16)
+}
+
+/// Adds the header to a palette in a color type's native format.
+pub fn palette_data(native_palette: &[u8], num_colors: usize, layout: &RawLayout) -> Vec<u8> {
+    let mut data = Vec::with_capacity(header_size(layout, 8) + native_palette.len());
+    if layout.header {
+        data.extend_from_slice(PALETTE_MAGIC);
+        data.extend_from_slice(&(num_colors as u16).to_le_bytes());
+        data.push((native_palette.len() / num_colors) as u8);
+        data.push(0);
+    }
+    data.extend_from_slice(native_palette);
+    data
+}
+
+fn header_size(layout: &RawLayout, size: usize) -> usize {
+    if layout.header {
+        size
+    } else {
+        0
+    }
+}
+
+/// Reads indexes written by `index_data` with a header, unpacking them to a
+/// byte each, along with the width and height.
+pub fn read_index_data(bytes: &[u8]) -> io::Result<(Vec<u8>, u32, u32)> {
+    let invalid = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    if bytes.len() < 12 || &bytes[..4] != INDEX_MAGIC {
+        return invalid("raw indexes need a header to be read; write them with --raw-header");
+    }
+    let width = u32::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+    let height = u32::from(u16::from_le_bytes([bytes[6], bytes[7]]));
+    let stride = usize::from(u16::from_le_bytes([bytes[8], bytes[9]]));
+    let bits_per_index = bytes[10];
+    let low_nibble_first = bytes[11] != 0;
+
+    if bits_per_index != 4 && bits_per_index != 8 {
+        return invalid(&format!("{} bit indexes aren't supported", bits_per_index));
+    }
+    if width == 0 || stride == 0 {
+        return invalid("the header gives rows of no width");
+    }
+    if stride < row_bytes(width, bits_per_index) {
+        return invalid("the header gives rows fewer bytes than their indexes need");
+    }
+    if bytes.len() - 12 < stride * height as usize {
+        return invalid("the indexes are shorter than the header says");
+    }
+
+    let mut indexes = Vec::with_capacity(width as usize * height as usize);
+    for row in bytes[12..].chunks(stride).take(height as usize) {
+        if bits_per_index == 8 {
+            indexes.extend_from_slice(&row[..width as usize]);
+        } else {
+            for x in 0..width as usize {
+                let byte = row[x / 2];
+                let first_nibble = (x % 2 == 0) != low_nibble_first;
+                indexes.push(if first_nibble { byte >> 4 } else { byte & 0x0F });
+            }
+        }
+    }
+    Ok((indexes, width, height))
+}
@@ -0,0 +1,231 @@
+//! Maps images onto an existing palette instead of clustering a new one.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image_lib::RgbaImage;
+
+use color::combination::{ColorCombination, ConvertibleColorCombination, Metric};
+use color::{Color, ConvertibleColor, Pixel, Rgba8};
+use k_means::SimpleInput;
+use options::Settings;
+use palette;
+
+use super::TRANSPARENT;
+
+/// Finds the palette index for each pixel, returning the palette entries as
+/// color combinations in their original order along with the indexes.
+///
+/// When index 0 is reserved for transparency, the palette's first entry is
+/// replaced by transparency.
+pub fn remap_images<O: Color>(
+    images: &[RgbaImage],
+    metric: &Metric,
+    settings: &Settings,
+    context: O::Context,
+) -> io::Result<(Vec<Vec<Pixel>>, Vec<u8>)> {
+    let palettes = read_palettes::<O>(&settings.remap, images.len(), context)?;
+    let reserves_transparent_index = settings.reserves_transparent_index();
+    if reserves_transparent_index && palettes[0].len() < 2 {
+        let message = "Reserving palette index 0 for transparency needs a palette of at least 2 \
+                       colors.";
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+
+    let mut centers: Vec<ColorCombination<O>> = (0..palettes[0].len())
+        .map(|entry| {
+            ColorCombination::new(
+                (0..images.len())
+                    .map(|image_index| {
+                        let palette = &palettes[image_index % palettes.len()];
+                        O::new(Rgba8::from(palette[entry]).components(), context)
+                    })
+                    .collect(),
+                metric,
+            )
+        })
+        .collect();
+    if reserves_transparent_index {
+        let transparent = O::new(Rgba8::from(TRANSPARENT).components(), context);
+        centers[0] = ColorCombination::new(vec![transparent; images.len()], metric);
+    }
+
+    let indexes = if settings.dither {
+        dithered_indexes(images, &centers, reserves_transparent_index, context)
+    } else {
+        nearest_indexes(images, &centers, reserves_transparent_index, context)
+    };
+    let color_combinations = centers.iter().map(ColorCombination::as_pixels).collect();
+    Ok((color_combinations, indexes))
+}
+
+/// Reads one palette for all of the images or one for each, all with the
+/// same number of colors.
+fn read_palettes<O: Color>(
+    paths: &[PathBuf],
+    num_images: usize,
+    context: O::Context,
+) -> io::Result<Vec<Vec<Pixel>>> {
+    if paths.len() != 1 && paths.len() != num_images {
+        let message = format!(
+            "{} palettes were given for {} images; give one for all of them or one for each.",
+            paths.len(),
+            num_images
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+
+    let mut palettes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let colors = read_palette::<O>(path, context)?;
+        if colors.len() > 256 {
+            let message = format!(
+                "{} has {} colors, but at most 256 are supported.",
+                path.display(),
+                colors.len()
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        match palettes.first().map(Vec::len) {
+            Some(first_len) if first_len != colors.len() => {
+                let message = format!(
+                    "{} has {} colors, but {} has {}; the palettes must be the same size.",
+                    path.display(),
+                    colors.len(),
+                    paths[0].display(),
+                    first_len
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+            _ => {}
+        }
+        palettes.push(colors);
+    }
+    Ok(palettes)
+}
+
+/// Reads a palette in any of the supported formats, reading raw palettes
+/// (.bin or .raw) in the color type's native format.
+pub fn read_palette<O: Color>(path: &Path, context: O::Context) -> io::Result<Vec<Pixel>> {
+    let is_raw = path.extension().map_or(false, |extension| {
+        extension.eq_ignore_ascii_case("bin") || extension.eq_ignore_ascii_case("raw")
+    });
+    if is_raw {
+        palette::read_native::<O>(path, context)
+    } else {
+        palette::read(path)
+    }
+}
+
+/// The index of the palette entry nearest to a pixel's colors across the
+/// images.
+fn nearest_index<'m, O: Color>(
+    pixels: &[Pixel],
+    centers: &[ColorCombination<'m, O>],
+    reserves_transparent_index: bool,
+    context: O::Context,
+) -> u8 {
+    let combination = ConvertibleColorCombination::<Rgba8, O>::new(
+        pixels
+            .iter()
+            .map(|&pixel| ConvertibleColor::new(pixel, context))
+            .collect(),
+        centers[0].metric(),
+    );
+    if !reserves_transparent_index {
+        combination.nearest(centers) as u8
+    } else if pixels.iter().all(|pixel| pixel[3] == 0) {
+        0
+    } else {
+        combination.nearest(&centers[1..]) as u8 + 1
+    }
+}
+
+/// The index of the nearest palette entry for each pixel, without dithering.
+pub fn nearest_indexes<O: Color>(
+    images: &[RgbaImage],
+    centers: &[ColorCombination<O>],
+    reserves_transparent_index: bool,
+    context: O::Context,
+) -> Vec<u8> {
+    let width = images[0].width();
+    let height = images[0].height();
+
+    let mut cache = HashMap::new();
+    let mut indexes = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let pixels: Vec<Pixel> = images.iter().map(|image| *image.get_pixel(x, y)).collect();
+            let index = *cache.entry(pixels).or_insert_with_key(|pixels| {
+                nearest_index(pixels, centers, reserves_transparent_index, context)
+            });
+            indexes.push(index);
+        }
+    }
+    indexes
+}
+
+/// Finds the indexes with Floyd-Steinberg dithering, spreading the error in
+/// each image's red, green and blue to the pixels not yet mapped. Alpha isn't
+/// dithered, and transparent pixels don't spread any error.
+pub fn dithered_indexes<O: Color>(
+    images: &[RgbaImage],
+    centers: &[ColorCombination<O>],
+    reserves_transparent_index: bool,
+    context: O::Context,
+) -> Vec<u8> {
+    let width = images[0].width() as usize;
+    let height = images[0].height() as usize;
+    let palette: Vec<Vec<Pixel>> = centers.iter().map(ColorCombination::as_pixels).collect();
+
+    let mut errors = vec![vec![[0.0f64; 3]; width * height]; images.len()];
+    let mut cache = HashMap::new();
+    let mut indexes = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let position = y * width + x;
+            let pixels: Vec<Pixel> = images
+                .iter()
+                .zip(errors.iter())
+                .map(|(image, errors)| {
+                    let mut pixel = *image.get_pixel(x as u32, y as u32);
+                    for channel in 0..3 {
+                        let value = f64::from(pixel[channel]) + errors[position][channel];
+                        pixel[channel] = value.round().clamp(0.0, 255.0) as u8;
+                    }
+                    pixel
+                })
+                .collect();
+            let index = *cache.entry(pixels.clone()).or_insert_with_key(|pixels| {
+                nearest_index(pixels, centers, reserves_transparent_index, context)
+            });
+            indexes.push(index);
+
+            for ((pixel, chosen), errors) in pixels
+                .iter()
+                .zip(palette[index as usize].iter())
+                .zip(errors.iter_mut())
+            {
+                if pixel[3] == 0 {
+                    continue;
+                }
+                for channel in 0..3 {
+                    let error = f64::from(pixel[channel]) - f64::from(chosen[channel]);
+                    let mut spread = |x: usize, y: usize, weight: f64| {
+                        if x < width && y < height {
+                            errors[y * width + x][channel] += error * weight;
+                        }
+                    };
+                    spread(x + 1, y, 7.0 / 16.0);
+                    if x > 0 {
+                        spread(x - 1, y + 1, 3.0 / 16.0);
+                    }
+                    spread(x, y + 1, 5.0 / 16.0);
+                    spread(x + 1, y + 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+    indexes
+}
@@ -0,0 +1,177 @@
+//! Measures how closely the quantized images match the originals.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use image_lib::RgbaImage;
+use ordered_float::NotNan;
+
+use color::{AlphaDistance, Color, Rgba8};
+use json;
+
+/// How many of the worst pixels to list for each image.
+const WORST_PIXELS: usize = 5;
+
+/// The SSIM window size, and the step between windows.
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+pub struct ImageReport {
+    pub name: String,
+    /// The mean and max distance per pixel, as used for clustering.
+    pub mean_error: f64,
+    pub max_error: f64,
+    pub psnr: f64,
+    pub ssim: f64,
+    /// The number of distinct palette entries the quantized image uses. Two
+    /// entries may hold the same color in one image of a set but not another.
+    pub colors_used: usize,
+    /// The pixels with the largest error, as (x, y, error), worst first.
+    pub worst_pixels: Vec<(u32, u32, f64)>,
+}
+
+pub fn image_report(
+    name: &str,
+    original: &RgbaImage,
+    quantized: &RgbaImage,
+    indexes: &[u8],
+    alpha_distance: AlphaDistance,
+) -> ImageReport {
+    let mut errors: Vec<(u32, u32, f64)> = original
+        .enumerate_pixels()
+        .map(|(x, y, &pixel)| {
+            let original_color = Rgba8::from(pixel);
+            let quantized_color = Rgba8::from(*quantized.get_pixel(x, y));
+            (
+                x,
+                y,
+                alpha_distance.between(&original_color, &quantized_color),
+            )
+        })
+        .collect();
+
+    let total_error: f64 = errors.iter().map(|&(_, _, error)| error).sum();
+    let mean_error = total_error / errors.len() as f64;
+
+    errors.sort_by_key(|&(_, _, error)| ::std::cmp::Reverse(NotNan::new(error).unwrap()));
+    errors.truncate(WORST_PIXELS);
+    let max_error = errors.first().map_or(0.0, |&(_, _, error)| error);
+
+    let colors_used = indexes.iter().collect::<HashSet<_>>().len();
+
+    ImageReport {
+        name: name.to_string(),
+        mean_error,
+        max_error,
+        psnr: super::psnr(mean_error),
+        ssim: ssim(original, quantized),
+        colors_used,
+        worst_pixels: errors,
+    }
+}
+
+pub fn print_reports(reports: &[ImageReport]) {
+    for report in reports {
+        println!("{}:", report.name);
+        println!(
+            "  mean error {:.6}, max error {:.6}, PSNR {:.2} dB, SSIM {:.4}",
+            report.mean_error, report.max_error, report.psnr, report.ssim
+        );
+        println!("  {} colors used", report.colors_used);
+        let worst_pixels: Vec<String> = report
+            .worst_pixels
+            .iter()
+            .map(|&(x, y, error)| format!("({}, {}) {:.6}", x, y, error))
+            .collect();
+        println!("  worst pixels: {}", worst_pixels.join(", "));
+    }
+}
+
+pub fn write_json_reports(path: &Path, reports: &[ImageReport]) -> io::Result<()> {
+    let images = json::array(reports.iter().map(|report| {
+        let worst_pixels = json::array(report.worst_pixels.iter().map(|&(x, y, error)| {
+            format!(
+                "{{\"x\": {}, \"y\": {}, \"error\": {}}}",
+                x,
+                y,
+                json::number(error)
+            )
+        }));
+        format!(
+            "{{\"name\": {}, \"mean_error\": {}, \"max_error\": {}, \"psnr\": {}, \
+             \"ssim\": {}, \"colors_used\": {}, \"worst_pixels\": {}}}",
+            json::string(&report.name),
+            json::number(report.mean_error),
+            json::number(report.max_error),
+            json::number(report.psnr),
+            json::number(report.ssim),
+            report.colors_used,
+            worst_pixels
+        )
+    }));
+
+    let mut file = File::create(path)?;
+    writeln!(file, "{{\"images\": {}}}", images)
+}
+
+/// The mean structural similarity of two images.
+///
+/// This compares luma over 8x8 windows. Colors are composited over black
+/// first, so differences in alpha count too.
+fn ssim(first: &RgbaImage, second: &RgbaImage) -> f64 {
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    let first_luma = composited_luma(first);
+    let second_luma = composited_luma(second);
+    let width = first.width();
+    let height = first.height();
+    let window_width = SSIM_WINDOW.min(width);
+    let window_height = SSIM_WINDOW.min(height);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..=height - window_height).step_by(SSIM_STEP as usize) {
+        for left in (0..=width - window_width).step_by(SSIM_STEP as usize) {
+            let indexes: Vec<usize> = (top..top + window_height)
+                .flat_map(|y| (left..left + window_width).map(move |x| (y * width + x) as usize))
+                .collect();
+            let n = indexes.len() as f64;
+
+            let mean_1 = indexes.iter().map(|&i| first_luma[i]).sum::<f64>() / n;
+            let mean_2 = indexes.iter().map(|&i| second_luma[i]).sum::<f64>() / n;
+            let mut variance_1 = 0.0;
+            let mut variance_2 = 0.0;
+            let mut covariance = 0.0;
+            for &i in &indexes {
+                let difference_1 = first_luma[i] - mean_1;
+                let difference_2 = second_luma[i] - mean_2;
+                variance_1 += difference_1 * difference_1;
+                variance_2 += difference_2 * difference_2;
+                covariance += difference_1 * difference_2;
+            }
+            variance_1 /= n;
+            variance_2 /= n;
+            covariance /= n;
+
+            total += ((2.0 * mean_1 * mean_2 + C1) * (2.0 * covariance + C2))
+                / ((mean_1 * mean_1 + mean_2 * mean_2 + C1) * (variance_1 + variance_2 + C2));
+            windows += 1;
+        }
+    }
+
+    total / f64::from(windows)
+}
+
+fn composited_luma(image: &RgbaImage) -> Vec<f64> {
+    image
+        .pixels()
+        .map(|&pixel| {
+            let (r, g, b, a) = Rgba8::from(pixel).components();
+            (0.299 * r + 0.587 * g + 0.114 * b) * a
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+//! Writes the indexes and palettes as source code, to build them into a
+//! program.
+
+use options::SourceLanguage;
+
+/// The indexes shared by all of the images and each image's native palette,
+/// ready to write as source code.
+pub struct SourceData<'a> {
+    pub indexes: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub palettes: &'a [Vec<u32>],
+    /// The size of each palette value in bytes.
+    pub value_size: usize,
+}
+
+pub fn source_code(
+    language: SourceLanguage,
+    data: &SourceData,
+    prefix: &str,
+    values_per_line: usize,
+) -> String {
+    match language {
+        SourceLanguage::C => c_header(data, prefix, values_per_line),
+        SourceLanguage::Rust => rust_module(data, prefix, values_per_line),
+    }
+}
+
+fn c_header(data: &SourceData, prefix: &str, values_per_line: usize) -> String {
+    let macro_prefix = prefix.to_uppercase();
+    let guard = format!("{}COLOR_SMASH_H", macro_prefix);
+    let value_type = match data.value_size {
+        1 => "uint8_t",
+        2 => "uint16_t",
+        _ => "uint32_t",
+    };
+
+    let mut text = format!(
+        "/* Generated by color_smash. */\n\n\
+         #ifndef {guard}\n#define {guard}\n\n#include <stdint.h>\n\n",
+        guard = guard
+    );
+    text += &format!("#define {}WIDTH {}\n", macro_prefix, data.width);
+    text += &format!("#define {}HEIGHT {}\n", macro_prefix, data.height);
+    text += &format!(
+        "#define {}PALETTE_SIZE {}\n\n",
+        macro_prefix,
+        data.palettes[0].len()
+    );
+
+    text += &format!(
+        "static const uint8_t {}idx[{}] = {{\n",
+        prefix,
+        data.indexes.len()
+    );
+    text += &values(
+        data.indexes.iter().map(|&index| u32::from(index)),
+        1,
+        values_per_line,
+    );
+    text += "};\n";
+    for (image_index, palette) in data.palettes.iter().enumerate() {
+        text += &format!(
+            "\nstatic const {} {}pal_{}[{}] = {{\n",
+            value_type,
+            prefix,
+            image_index,
+            palette.len()
+        );
+        text += &values(palette.iter().cloned(), data.value_size, values_per_line);
+        text += "};\n";
+    }
+
+    text += &format!("\n#endif /* {} */\n", guard);
+    text
+}
+
+fn rust_module(data: &SourceData, prefix: &str, values_per_line: usize) -> String {
+    let prefix = prefix.to_uppercase();
+    let value_type = match data.value_size {
+        1 => "u8",
+        2 => "u16",
+        _ => "u32",
+    };
+
+    let mut text = "//! Generated by color_smash.\n\n".to_string();
+    text += &format!("pub const {}WIDTH: usize = {};\n", prefix, data.width);
+    text += &format!("pub const {}HEIGHT: usize = {};\n\n", prefix, data.height);
+
+    text += &format!(
+        "pub static {}IDX: [u8; {}] = [\n",
+        prefix,
+        data.indexes.len()
+    );
+    text += &values(
+        data.indexes.iter().map(|&index| u32::from(index)),
+        1,
+        values_per_line,
+    );
+    text += "];\n";
+    for (image_index, palette) in data.palettes.iter().enumerate() {
+        text += &format!(
+            "\npub static {}PAL_{}: [{}; {}] = [\n",
+            prefix,
+            image_index,
+            value_type,
+            palette.len()
+        );
+        text += &values(palette.iter().cloned(), data.value_size, values_per_line);
+        text += "];\n";
+    }
+    text
+}
+
+/// Lists values in hex, indented, with a comma after each.
+fn values<I: Iterator<Item = u32>>(values: I, size: usize, values_per_line: usize) -> String {
+    let values: Vec<String> = values
+        .map(|value| format!("0x{:01$X},", value, size * 2))
+        .collect();
+    values
+        .chunks(values_per_line)
+        .map(|line| format!("    {}\n", line.join(" ")))
+        .collect()
+}
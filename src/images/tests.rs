@@ -1,9 +1,23 @@
-use super::quantization_map_from_images;
+use super::bundle::{manifest, BundledImage};
+use super::compose::compose_image;
+use super::heatmap::{composite, heat_color, max_error};
+use super::importance::{auto_weights, mask_weights};
+use super::raw;
+use super::remap::{dithered_indexes, nearest_indexes};
+use super::report::image_report;
+use super::source::{source_code, SourceData};
+use super::{
+    clear_transparent, metric, native_palette_values, palettes_for_indexes, prepare_images,
+    quantization_map_from_images, TRANSPARENT,
+};
+use color::combination::{ColorCombination, ConvertibleColorCombination, Metric};
 use color::*;
+use options::{AlphaMode, ColorType, RawLayout, Settings, SourceLanguage, MAX_FACTOR};
 
 use image_lib;
-use image_lib::RgbaImage;
+use image_lib::{GrayImage, RgbaImage};
 use std::collections::HashSet;
+use std::io;
 use std::path::Path;
 
 use test::Bencher;
@@ -14,7 +28,17 @@ fn load_test_image() -> RgbaImage {
 
 fn has_n_colors(n: u32) {
     let image = load_test_image();
-    let quantization_map = quantization_map_from_images::<Rgb5a3>(&vec![image], n, false);
+    let settings = Settings {
+        num_colors: n,
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Rgb5a3>(
+        &vec![image],
+        None,
+        &Metric::default(),
+        &settings,
+        (),
+    );
     let mut colors = HashSet::new();
     for color in quantization_map.values() {
         colors.insert(color);
@@ -35,7 +59,13 @@ fn has_7_colors() {
 #[test]
 fn rgb_is_zero_if_alpha_is() {
     let image = load_test_image();
-    let quantization_map = quantization_map_from_images::<Rgb5a3>(&vec![image], 256, false);
+    let quantization_map = quantization_map_from_images::<Rgb5a3>(
+        &vec![image],
+        None,
+        &Metric::default(),
+        &Settings::default(),
+        (),
+    );
     for colors in quantization_map
         .values()
         .into_iter()
@@ -49,9 +79,679 @@ fn rgb_is_zero_if_alpha_is() {
     }
 }
 
+#[test]
+fn identical_images_have_no_error() {
+    let image = RgbaImage::from_fn(16, 16, |x, y| Pixel {
+        data: [(x * 16) as u8, (y * 16) as u8, 0x80, 0xFF],
+    });
+    let indexes: Vec<u8> = (0..=255).collect();
+    let report = image_report("test", &image, &image, &indexes, AlphaDistance::default());
+    assert_eq!(report.mean_error, 0.0);
+    assert_eq!(report.max_error, 0.0);
+    assert!(report.psnr.is_infinite());
+    assert!((report.ssim - 1.0).abs() < 1e-9);
+    assert_eq!(report.colors_used, 256);
+}
+
+#[test]
+fn heat_colors_go_from_black_through_red_and_yellow_to_white() {
+    let expected = [
+        (-1.0, [0x00, 0x00, 0x00, 0xFF]),
+        (0.0, [0x00, 0x00, 0x00, 0xFF]),
+        (1.0 / 3.0, [0xFF, 0x00, 0x00, 0xFF]),
+        (0.5, [0xFF, 0x80, 0x00, 0xFF]),
+        (2.0 / 3.0, [0xFF, 0xFF, 0x00, 0xFF]),
+        (1.0, [0xFF, 0xFF, 0xFF, 0xFF]),
+        (2.0, [0xFF, 0xFF, 0xFF, 0xFF]),
+    ];
+    for &(value, data) in &expected {
+        assert_eq!(heat_color(value).data, data, "heat color of {}", value);
+    }
+}
+
+#[test]
+fn max_error_is_the_largest_over_every_image() {
+    let black = Pixel {
+        data: [0x00, 0x00, 0x00, 0xFF],
+    };
+    let white = Pixel { data: [0xFF; 4] };
+    let red = Pixel {
+        data: [0xFF, 0x00, 0x00, 0xFF],
+    };
+    let originals = [
+        RgbaImage::from_pixel(2, 1, black),
+        RgbaImage::from_pixel(2, 1, black),
+    ];
+    let mut first = RgbaImage::from_pixel(2, 1, black);
+    first.put_pixel(1, 0, red);
+    let mut second = RgbaImage::from_pixel(2, 1, black);
+    second.put_pixel(0, 0, white);
+
+    let alpha_distance = AlphaDistance::default();
+    assert_eq!(
+        max_error(&originals[..1], &[first.clone()], alpha_distance),
+        1.0
+    );
+    assert_eq!(max_error(&originals, &[first, second], alpha_distance), 3.0);
+    assert_eq!(max_error(&originals, &originals, alpha_distance), 0.0);
+}
+
+#[test]
+fn composite_places_the_images_side_by_side() {
+    let images: Vec<_> = (0..3)
+        .map(|index| RgbaImage::from_pixel(5, 3, Pixel { data: [index; 4] }))
+        .collect();
+    let composed = composite(&images[0], &images[1], &images[2]).unwrap();
+    assert_eq!(composed.dimensions(), (15, 3));
+    for (index, image) in images.iter().enumerate() {
+        let x = 5 * index as u32;
+        assert_eq!(composed.get_pixel(x, 2), image.get_pixel(0, 0));
+        assert_eq!(composed.get_pixel(x + 4, 0), image.get_pixel(4, 2));
+    }
+
+    let tall = RgbaImage::new(5, 4);
+    assert!(composite(&images[0], &images[1], &tall).is_err());
+}
+
+#[test]
+fn colors_used_counts_palette_entries() {
+    let image = RgbaImage::from_pixel(4, 4, Pixel { data: [0x80; 4] });
+    let indexes: Vec<u8> = (0..16).map(|index| index % 2).collect();
+    let report = image_report("test", &image, &image, &indexes, AlphaDistance::default());
+    assert_eq!(report.colors_used, 2);
+}
+
+#[test]
+fn transparent_pixels_keep_their_own_color() {
+    let image = RgbaImage::from_fn(16, 16, |x, y| {
+        if x < 4 {
+            TRANSPARENT
+        } else {
+            Pixel {
+                data: [(x * 16) as u8, (y * 16) as u8, 0x80, 0xFF],
+            }
+        }
+    });
+    let settings = Settings {
+        colortype: ColorType::Bgr555,
+        num_colors: 4,
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Bgr555>(
+        &vec![image],
+        None,
+        &Metric::default(),
+        &settings,
+        (),
+    );
+
+    assert_eq!(quantization_map[&vec![TRANSPARENT]], vec![TRANSPARENT]);
+    let opaque_colors: HashSet<_> = quantization_map
+        .iter()
+        .filter(|&(key, _)| key[0] != TRANSPARENT)
+        .map(|(_, colors)| colors)
+        .collect();
+    assert_eq!(opaque_colors.len(), 3);
+    assert!(opaque_colors.iter().all(|colors| colors[0].data[3] == 0xFF));
+}
+
+fn prepare_with_alpha_mode(alpha: AlphaMode) -> Vec<Pixel> {
+    let image = RgbaImage::from_vec(
+        3,
+        1,
+        vec![
+            0xC8, 0x64, 0x00, 0x40, // mostly transparent
+            0x0A, 0x14, 0x1E, 0xC0, // mostly opaque
+            0x30, 0x60, 0x90, 0xFF, // opaque
+        ],
+    )
+    .unwrap();
+    let settings = Settings {
+        alpha,
+        ..Settings::default()
+    };
+    let images = prepare_images(&[image], &[Path::new("test.png")], &settings).unwrap();
+    images[0].pixels().cloned().collect()
+}
+
+#[test]
+fn keep_and_premultiplied_alpha_modes_leave_pixels_alone() {
+    let expected = vec![
+        Pixel {
+            data: [0xC8, 0x64, 0x00, 0x40],
+        },
+        Pixel {
+            data: [0x0A, 0x14, 0x1E, 0xC0],
+        },
+        Pixel {
+            data: [0x30, 0x60, 0x90, 0xFF],
+        },
+    ];
+    assert_eq!(prepare_with_alpha_mode(AlphaMode::Keep), expected);
+    assert_eq!(prepare_with_alpha_mode(AlphaMode::Premultiplied), expected);
+}
+
+#[test]
+fn threshold_alpha_mode_makes_pixels_transparent_or_opaque() {
+    assert_eq!(
+        prepare_with_alpha_mode(AlphaMode::Threshold),
+        vec![
+            TRANSPARENT,
+            Pixel {
+                data: [0x0A, 0x14, 0x1E, 0xFF],
+            },
+            Pixel {
+                data: [0x30, 0x60, 0x90, 0xFF],
+            },
+        ]
+    );
+}
+
+#[test]
+fn matte_alpha_mode_composites_pixels_over_the_matte() {
+    let matte = Pixel {
+        data: [0xFF, 0xFF, 0xFF, 0xFF],
+    };
+    assert_eq!(
+        prepare_with_alpha_mode(AlphaMode::Matte(matte)),
+        vec![
+            Pixel {
+                data: [0xF1, 0xD8, 0xBF, 0xFF],
+            },
+            Pixel {
+                data: [0x47, 0x4E, 0x56, 0xFF],
+            },
+            Pixel {
+                data: [0x30, 0x60, 0x90, 0xFF],
+            },
+        ]
+    );
+}
+
+#[test]
+fn premultiplied_alpha_mode_groups_by_premultiplied_color() {
+    let black = Pixel {
+        data: [0x00, 0x00, 0x00, 0xFF],
+    };
+    let white = Pixel {
+        data: [0xFF, 0xFF, 0xFF, 0xFF],
+    };
+    // Nearer white as it is, but nearer black once scaled by its alpha.
+    let translucent_gray = Pixel {
+        data: [0x99, 0x99, 0x99, 0x80],
+    };
+    let image = RgbaImage::from_fn(16, 16, |x, y| match (x, y) {
+        (0, 0) => translucent_gray,
+        (x, _) if x < 8 => black,
+        _ => white,
+    });
+
+    for &(alpha, nearest) in &[(AlphaMode::Keep, white), (AlphaMode::Premultiplied, black)] {
+        let settings = Settings {
+            alpha,
+            num_colors: 2,
+            ..Settings::default()
+        };
+        let images = prepare_images(&[image.clone()], &[Path::new("test.png")], &settings).unwrap();
+        let quantization_map =
+            quantization_map_from_images::<Rgba8>(&images, None, &metric(&settings), &settings, ());
+        assert_eq!(
+            quantization_map[&vec![translucent_gray]],
+            quantization_map[&vec![nearest]]
+        );
+    }
+}
+
+#[test]
+fn transparent_index_takes_one_of_the_colors() {
+    let mut image = RgbaImage::from_fn(16, 16, |x, y| Pixel {
+        data: [
+            (x * 16) as u8,
+            (y * 16) as u8,
+            0x80,
+            if x < 4 { 0 } else { 0xFF },
+        ],
+    });
+    clear_transparent(&mut image);
+    let settings = Settings {
+        num_colors: 4,
+        transparent_index: true,
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Rgba8>(
+        &vec![image],
+        None,
+        &Metric::default(),
+        &settings,
+        (),
+    );
+
+    assert_eq!(quantization_map[&vec![TRANSPARENT]], vec![TRANSPARENT]);
+    let other_colors: HashSet<_> = quantization_map
+        .iter()
+        .filter(|&(key, _)| key[0] != TRANSPARENT)
+        .map(|(_, colors)| colors)
+        .collect();
+    assert_eq!(other_colors.len(), 3);
+    assert!(!other_colors.contains(&vec![TRANSPARENT]));
+}
+
+#[test]
+fn transparent_index_is_only_taken_for_transparent_pixels() {
+    let image = RgbaImage::from_fn(16, 16, |x, y| Pixel {
+        data: [(x * 16) as u8, (y * 16) as u8, 0x80, 0xFF],
+    });
+    let settings = Settings {
+        num_colors: 4,
+        transparent_index: true,
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Rgba8>(
+        &vec![image],
+        None,
+        &Metric::default(),
+        &settings,
+        (),
+    );
+
+    let colors: HashSet<_> = quantization_map.values().collect();
+    assert_eq!(colors.len(), 4);
+    assert!(!colors.contains(&vec![TRANSPARENT]));
+}
+
+/// Centers for a palette shared by a single image, in the palette's order.
+fn palette_centers<'m>(palette: &[Pixel], metric: &'m Metric) -> Vec<ColorCombination<'m, Rgba8>> {
+    palette
+        .iter()
+        .map(|&pixel| ColorCombination::new(vec![Rgba8::from(pixel)], metric))
+        .collect()
+}
+
+#[test]
+fn remapping_picks_the_nearest_entry_in_the_palettes_order() {
+    let palette = [
+        Pixel { data: [0xFF; 4] },
+        Pixel {
+            data: [0x00, 0x00, 0x00, 0xFF],
+        },
+        Pixel {
+            data: [0xFF, 0x00, 0x00, 0xFF],
+        },
+        Pixel {
+            data: [0x00, 0x00, 0xFF, 0xFF],
+        },
+    ];
+    let pixels = [
+        [0x10, 0x20, 0xE0, 0xFF],
+        [0xF0, 0xE0, 0xF0, 0xFF],
+        [0xD0, 0x30, 0x20, 0xFF],
+        [0x20, 0x10, 0x30, 0xFF],
+    ];
+    let image = RgbaImage::from_fn(4, 1, |x, _| Pixel {
+        data: pixels[x as usize],
+    });
+    let metric = Metric::default();
+    let centers = palette_centers(&palette, &metric);
+    assert_eq!(
+        nearest_indexes(&[image], &centers, false, ()),
+        vec![3, 0, 2, 1]
+    );
+}
+
+#[test]
+fn dithering_mixes_the_nearest_entries_in_proportion() {
+    let palette = [
+        Pixel {
+            data: [0x00, 0x00, 0x00, 0xFF],
+        },
+        Pixel { data: [0xFF; 4] },
+    ];
+    let metric = Metric::default();
+    let centers = palette_centers(&palette, &metric);
+    for &gray in &[0x40u8, 0x80, 0xC0] {
+        let image = RgbaImage::from_pixel(
+            32,
+            32,
+            Pixel {
+                data: [gray, gray, gray, 0xFF],
+            },
+        );
+        let indexes = dithered_indexes(&[image], &centers, false, ());
+        let white = indexes.iter().filter(|&&index| index == 1).count();
+        let white_share = white as f64 / indexes.len() as f64;
+        let expected = f64::from(gray) / 255.0;
+        assert!(
+            (white_share - expected).abs() < 0.02,
+            "{:02X} gray was {} white",
+            gray,
+            white_share
+        );
+    }
+}
+
+#[test]
+fn raw_indexes_pack_and_pad_rows() {
+    let indexes = [1, 2, 3, 4, 5, 6];
+    let mut layout = RawLayout {
+        bits_per_index: 4,
+        low_nibble_first: false,
+        row_alignment: 4,
+        header: false,
+    };
+    assert_eq!(
+        raw::index_data(&indexes, 3, 2, &layout).unwrap(),
+        vec![0x12, 0x30, 0, 0, 0x45, 0x60, 0, 0]
+    );
+
+    layout.low_nibble_first = true;
+    layout.header = true;
+    assert_eq!(
+        raw::index_data(&indexes, 3, 2, &layout).unwrap(),
+        vec![b'C', b'S', b'I', b'X', 3, 0, 2, 0, 4, 0, 4, 1, 0x21, 0x03, 0, 0, 0x54, 0x06, 0, 0,]
+    );
+}
+
+#[test]
+fn raw_indexes_round_trip_with_header() {
+    let indexes: Vec<u8> = (0..35).map(|index| index % 16).collect();
+    for &(bits_per_index, low_nibble_first) in &[(8, false), (4, false), (4, true)] {
+        let layout = RawLayout {
+            bits_per_index,
+            low_nibble_first,
+            row_alignment: 4,
+            header: true,
+        };
+        let data = raw::index_data(&indexes, 7, 5, &layout).unwrap();
+        assert_eq!(
+            raw::read_index_data(&data).unwrap(),
+            (indexes.clone(), 7, 5)
+        );
+    }
+}
+
+#[test]
+fn raw_indexes_without_header_cant_be_read() {
+    let data = raw::index_data(&[1, 2, 3, 4], 2, 2, &RawLayout::default()).unwrap();
+    assert!(raw::read_index_data(&data).is_err());
+}
+
+#[test]
+fn raw_index_headers_with_impossible_rows_are_rejected() {
+    // Width, height and bytes per row, for 8 bit indexes.
+    for &(width, height, stride) in &[(0, 2, 4), (2, 2, 0), (4, 2, 2)] {
+        let mut data = b"CSIX".to_vec();
+        for &number in &[width, height, stride] {
+            data.extend_from_slice(&(number as u16).to_le_bytes());
+        }
+        data.extend_from_slice(&[8, 0]);
+        data.resize(data.len() + 16, 0);
+        let error = raw::read_index_data(&data).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn raw_index_headers_cant_hold_more_than_16_bits() {
+    let layout = RawLayout {
+        header: true,
+        ..RawLayout::default()
+    };
+    assert!(raw::index_data(&[], 70000, 1, &layout).is_err());
+    let layout = RawLayout {
+        header: false,
+        ..RawLayout::default()
+    };
+    assert_eq!(raw::index_data(&[], 70000, 1, &layout).unwrap(), vec![]);
+}
+
+#[test]
+fn composing_looks_up_each_index() {
+    let palette = [
+        TRANSPARENT,
+        Pixel {
+            data: [0xFF, 0x00, 0x00, 0xFF],
+        },
+    ];
+    let image = compose_image(&[0, 1, 1, 0], 2, 2, &palette).unwrap();
+    assert_eq!(*image.get_pixel(1, 0), palette[1]);
+    assert_eq!(*image.get_pixel(1, 1), palette[0]);
+
+    assert!(compose_image(&[0, 2, 1, 0], 2, 2, &palette).is_err());
+}
+
+#[test]
+fn raw_palette_header_gives_color_size() {
+    let layout = RawLayout {
+        bits_per_index: 8,
+        low_nibble_first: false,
+        row_alignment: 1,
+        header: true,
+    };
+    assert_eq!(
+        raw::palette_data(&[0x12, 0x34, 0x56, 0x78], 2, &layout),
+        vec![b'C', b'S', b'P', b'L', 2, 0, 2, 0, 0x12, 0x34, 0x56, 0x78]
+    );
+}
+
+#[test]
+fn palettes_for_indexes_average_each_index() {
+    let image = RgbaImage::from_fn(4, 1, |x, _| Pixel {
+        data: [(x * 0x40) as u8, 0x80, 0x00, 0xFF],
+    });
+    let indexes = [1, 1, 2, 2];
+    let settings = Settings::default();
+    let palette = palettes_for_indexes::<Rgba8>(&[image], &indexes, None, &settings, ());
+
+    assert_eq!(palette.len(), 3);
+    assert_eq!(palette[0], vec![TRANSPARENT]);
+    assert_eq!(palette[1][0].data, [0x20, 0x80, 0x00, 0xFF]);
+    assert_eq!(palette[2][0].data, [0xA0, 0x80, 0x00, 0xFF]);
+}
+
+#[test]
+fn mask_weights_take_the_brightest_mask() {
+    let first = GrayImage::from_raw(3, 1, vec![0x00, 0xFF, 0x40]).unwrap();
+    let second = GrayImage::from_raw(3, 1, vec![0x00, 0x10, 0x80]).unwrap();
+    assert_eq!(mask_weights(&[first, second]), vec![1, 0xFF, 0x80]);
+}
+
+#[test]
+fn auto_weights_favor_edges_over_flat_regions() {
+    let image = RgbaImage::from_fn(6, 3, |x, _| {
+        let value = if x < 3 { 0x00 } else { 0xFF };
+        Pixel {
+            data: [value, value, value, 0xFF],
+        }
+    });
+    let weights = auto_weights(&[image], 1.0, 0.5);
+    // Flat pixels count half, and those on either side of the edge about
+    // 1.7 times.
+    assert_eq!(&weights[6..12], &[8, 8, 27, 27, 8, 8]);
+}
+
+#[test]
+fn large_fully_weighted_masks_dont_overflow_the_counts() {
+    let dark = Pixel {
+        data: [0x40, 0x40, 0x40, 0xFF],
+    };
+    let light = Pixel {
+        data: [0xC0, 0xC0, 0xC0, 0xFF],
+    };
+    let image = RgbaImage::from_fn(1024, 1024, |x, _| if x < 512 { dark } else { light });
+    let mask = GrayImage::from_pixel(1024, 1024, image_lib::Luma { data: [0xFF] });
+    // Counting flat pixels twice, the weights add up to more than u32::MAX.
+    let weights: Vec<u32> = auto_weights(&[image.clone()], 0.0, 2.0)
+        .iter()
+        .zip(mask_weights(&[mask]))
+        .map(|(auto_weight, mask_weight)| auto_weight * mask_weight)
+        .collect();
+    assert!(weights.iter().map(|&weight| u64::from(weight)).sum::<u64>() > u64::from(u32::MAX));
+
+    let settings = Settings {
+        num_colors: 1,
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Rgba8>(
+        &[image],
+        Some(&weights),
+        &Metric::default(),
+        &settings,
+        (),
+    );
+    assert_eq!(
+        quantization_map[&vec![dark]][0].data,
+        [0x80, 0x80, 0x80, 0xFF]
+    );
+}
+
+#[test]
+fn edge_mask_and_image_weights_combine_at_their_largest() {
+    let images: Vec<RgbaImage> = [0x00, 0x80]
+        .iter()
+        .map(|&blue| {
+            RgbaImage::from_fn(64, 64, |x, _| {
+                let value = if x < 32 { 0x20 } else { 0xE0 };
+                Pixel {
+                    data: [value, value, blue, 0xFF],
+                }
+            })
+        })
+        .collect();
+    let mask = GrayImage::from_pixel(64, 64, image_lib::Luma { data: [0xFF] });
+    let weights: Vec<u32> = auto_weights(&images, MAX_FACTOR, MAX_FACTOR)
+        .iter()
+        .zip(mask_weights(&[mask]))
+        .map(|(auto_weight, mask_weight)| auto_weight * mask_weight)
+        .collect();
+    assert!(weights.iter().map(|&weight| u64::from(weight)).sum::<u64>() > u64::from(u32::MAX));
+
+    let settings = Settings {
+        num_colors: 2,
+        weights: vec![1.0, 3.0],
+        ..Settings::default()
+    };
+    let quantization_map = quantization_map_from_images::<Rgba8>(
+        &images,
+        Some(&weights),
+        &metric(&settings),
+        &settings,
+        (),
+    );
+    for (combination, colors) in &quantization_map {
+        assert_eq!(combination, colors);
+    }
+}
+
+#[test]
+fn every_color_past_the_outlier_threshold_gets_its_own_entry() {
+    // Single pixels, which the run with the lowest total error of several
+    // restarts leaves to share entries with the grays.
+    let details = [
+        [0xC0, 0x40, 0x40, 0xFF],
+        [0x40, 0xC0, 0x40, 0xFF],
+        [0x40, 0x40, 0xC0, 0xFF],
+    ];
+    let image = RgbaImage::from_fn(32, 32, |x, y| {
+        let data = if y == 0 && (x as usize) < details.len() {
+            details[x as usize]
+        } else {
+            let value = ((x + y) * 4) as u8;
+            [value, value, value, 0xFF]
+        };
+        Pixel { data }
+    });
+    let threshold = 0.1;
+    let settings = Settings {
+        num_colors: 8,
+        restarts: 8,
+        outlier_threshold: Some(threshold),
+        ..Settings::default()
+    };
+    let quantization_map =
+        quantization_map_from_images::<Rgba8>(&[image], None, &metric(&settings), &settings, ());
+
+    for &data in &details {
+        assert_eq!(
+            quantization_map[&vec![Pixel { data }]],
+            vec![Pixel { data }]
+        );
+    }
+    for (colors, palette_colors) in &quantization_map {
+        let distance = AlphaDistance::default()
+            .between(&Rgba8::from(colors[0]), &Rgba8::from(palette_colors[0]));
+        assert!(distance <= threshold);
+    }
+}
+
+#[test]
+fn native_palette_values_follow_endianness() {
+    let red = Pixel {
+        data: [0xFF, 0x00, 0x00, 0xFF],
+    };
+    let combination = vec![red];
+    // RGB565 is big-endian and BGR555 little-endian, with red in the high
+    // and low bits respectively.
+    assert_eq!(
+        native_palette_values::<Rgb565>(&[&combination], ()),
+        (2, vec![vec![0xF800]])
+    );
+    assert_eq!(
+        native_palette_values::<Bgr555>(&[&combination], ()),
+        (2, vec![vec![0x001F]])
+    );
+}
+
+#[test]
+fn source_code_declares_indexes_and_palettes() {
+    let palettes = vec![vec![0x7FFF, 0x001F], vec![0x0000, 0x03E0]];
+    let data = SourceData {
+        indexes: &[0, 1, 1],
+        width: 3,
+        height: 1,
+        palettes: &palettes,
+        value_size: 2,
+    };
+
+    let header = source_code(SourceLanguage::C, &data, "hero_", 2);
+    assert!(header.contains("#define HERO_WIDTH 3\n"));
+    assert!(header.contains("static const uint8_t hero_idx[3] = {\n    0x00, 0x01,\n    0x01,\n};"));
+    assert!(header.contains("static const uint16_t hero_pal_1[2] = {\n    0x0000, 0x03E0,\n};"));
+
+    let module = source_code(SourceLanguage::Rust, &data, "hero_", 16);
+    assert!(module.contains("pub static HERO_IDX: [u8; 3] = [\n    0x00, 0x01, 0x01,\n];"));
+    assert!(module.contains("pub static HERO_PAL_0: [u16; 2] = [\n    0x7FFF, 0x001F,\n];"));
+}
+
+#[test]
+fn manifest_matches_palettes_to_images() {
+    let images = [
+        BundledImage {
+            source: "idle.png".to_string(),
+            palette: "idle (smashed).palette.hex".to_string(),
+            native_palette: None,
+        },
+        BundledImage {
+            source: "hurt.png".to_string(),
+            palette: "hurt (smashed).palette.hex".to_string(),
+            native_palette: Some("hurt (smashed).palette.bin".to_string()),
+        },
+    ];
+    assert_eq!(
+        manifest("idle (smashed).indexes.png", 8, 4, 16, "RGB5A3", &images),
+        "{\"indexes\": \"idle (smashed).indexes.png\", \"width\": 8, \"height\": 4, \
+         \"colors\": 16, \"color_type\": \"RGB5A3\", \"images\": [\
+         {\"source\": \"idle.png\", \"palette\": \"idle (smashed).palette.hex\"}, \
+         {\"source\": \"hurt.png\", \"palette\": \"hurt (smashed).palette.hex\", \
+         \"native_palette\": \"hurt (smashed).palette.bin\"}]}"
+    );
+}
+
 #[bench]
 fn bench_quantization_to_rgb5a3(b: &mut Bencher) {
     let image = load_test_image();
     let images = vec![image];
-    b.iter(|| quantization_map_from_images::<Rgb5a3>(&images, 256, false));
+    let settings = Settings::default();
+    b.iter(|| {
+        quantization_map_from_images::<Rgb5a3>(&images, None, &Metric::default(), &settings, ())
+    });
 }
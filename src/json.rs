@@ -0,0 +1,42 @@
+//! Helpers for writing JSON output.
+//!
+//! The documents we write are small and flat, so they're assembled with
+//! format! rather than through a serialization library; these just take care
+//! of the values that need escaping.
+
+use std::fmt::Write;
+
+/// Formats a string as a quoted, escaped JSON string.
+pub fn string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(output, "\\u{:04x}", c as u32).unwrap(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Formats a number, using null for infinities and NaN, which JSON can't
+/// represent.
+pub fn number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Formats a list of already formatted values as a JSON array.
+pub fn array<I: IntoIterator<Item = String>>(values: I) -> String {
+    let values: Vec<String> = values.into_iter().collect();
+    format!("[{}]", values.join(", "))
+}
@@ -2,67 +2,146 @@ use super::Input;
 use num::{Float, FromPrimitive, Zero};
 use ordered_float::NotNan;
 
-pub fn initialize_centers<I: Input>(k: u32, points: &[I]) -> (Vec<I::Output>, Vec<Vec<&I>>) {
-    let mut centers = Vec::with_capacity(k as usize);
-    let first_center = points
-        .iter()
-        .max_by_key(|point| point.count())
-        .unwrap()
-        .as_output();
-    centers.push(first_center);
+mod random;
+pub use self::random::initialize_centers_randomly;
 
-    let mut distance_per_point: Vec<_> = points
-        .iter()
-        .map(|point| point.normalized_distance(&centers[0]))
-        .collect();
-    let mut cluster_per_point: Vec<_> = vec![0; points.len()];
+pub fn initialize_centers<'a, I: Input>(
+    k: u32,
+    points: &'a [I],
+    fixed_centers: &[I::Output],
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>) {
+    let mut splitter = Splitter::new(points, fixed_centers);
+    while splitter.centers.len() < (k as usize) {
+        splitter.add_center();
+    }
+    splitter.into_clusters()
+}
 
-    let mut distance_per_cluster: Vec<_> = Vec::with_capacity(k as usize);
-    let distance_to_first_center = points
-        .iter()
-        .zip(distance_per_point.iter())
-        .map(|(point, distance)| *distance * I::Distance::from_u32(point.count()).unwrap())
-        .sum();
-    distance_per_cluster.push(distance_to_first_center);
-
-    while centers.len() < (k as usize) {
-        let cluster_to_split = worst_cluster(&distance_per_cluster);
-        let farthest_point_index =
-            farthest_point_of(cluster_to_split, &cluster_per_point, &distance_per_point);
-        let new_center = points[farthest_point_index].as_output();
-
-        if centers.iter().any(|center| *center == new_center) {
+/// The state of the initializer, which can keep adding centers one at a time.
+///
+/// Each new center splits the cluster with the greatest total distance, at
+/// the point farthest from its center.
+pub struct Splitter<'a, I: 'a + Input> {
+    points: &'a [I],
+    centers: Vec<I::Output>,
+    distance_per_point: Vec<I::Distance>,
+    cluster_per_point: Vec<usize>,
+    distance_per_cluster: Vec<I::Distance>,
+}
+
+impl<'a, I: Input> Splitter<'a, I> {
+    /// Starts with the fixed centers, or if there are none, a single center
+    /// at the most common point.
+    pub fn new(points: &'a [I], fixed_centers: &[I::Output]) -> Splitter<'a, I> {
+        let centers = if fixed_centers.is_empty() {
+            let first_center = points
+                .iter()
+                .max_by_key(|point| point.count())
+                .unwrap()
+                .as_output();
+            vec![first_center]
+        } else {
+            fixed_centers.to_vec()
+        };
+
+        let (cluster_per_point, distance_per_point) = nearest_centers(points, &centers);
+
+        let mut distance_per_cluster = vec![I::Distance::zero(); centers.len()];
+        for ((point, &cluster), &distance) in points
+            .iter()
+            .zip(cluster_per_point.iter())
+            .zip(distance_per_point.iter())
+        {
+            distance_per_cluster[cluster] = distance_per_cluster[cluster]
+                + distance * I::Distance::from_u64(point.count()).unwrap();
+        }
+
+        Splitter {
+            points,
+            centers,
+            distance_per_point,
+            cluster_per_point,
+            distance_per_cluster,
+        }
+    }
+
+    pub fn centers(&self) -> &[I::Output] {
+        &self.centers
+    }
+
+    pub fn add_center(&mut self) {
+        let cluster_to_split = worst_cluster(&self.distance_per_cluster);
+        // The worst cluster can only be empty when no point is any distance
+        // from its center, such as when every point is already a center.
+        let farthest_point_index = farthest_point_of(
+            cluster_to_split,
+            &self.cluster_per_point,
+            &self.distance_per_point,
+        )
+        .unwrap_or(0);
+        let new_center = self.points[farthest_point_index].as_output();
+
+        if self.centers.iter().any(|center| *center == new_center) {
             println!("Created duplicate center: {:?}", new_center);
         }
 
-        let new_cluster = centers.len();
+        let new_cluster = self.centers.len();
+        let distance_per_cluster = &mut self.distance_per_cluster;
         distance_per_cluster.push(I::Distance::zero());
 
-        for ((point, distance), cluster) in points
+        for ((point, distance), cluster) in self
+            .points
             .iter()
-            .zip(distance_per_point.iter_mut())
-            .zip(cluster_per_point.iter_mut())
+            .zip(self.distance_per_point.iter_mut())
+            .zip(self.cluster_per_point.iter_mut())
         {
             let new_distance = point.normalized_distance(&new_center);
             if new_distance < *distance {
                 // FIXME: -= doesn't work for num::Float
-                // distance_per_cluster[*cluster] -= *distance * I::Distance::from_u32(point.count()).unwrap();
+                // distance_per_cluster[*cluster] -= *distance * I::Distance::from_u64(point.count()).unwrap();
                 distance_per_cluster[*cluster] = distance_per_cluster[*cluster]
-                    - *distance * I::Distance::from_u32(point.count()).unwrap();
+                    - *distance * I::Distance::from_u64(point.count()).unwrap();
                 *cluster = new_cluster;
                 *distance = new_distance;
                 // FIXME: += doesn't work for num::Float
-                // distance_per_cluster[new_cluster] += new_distance * I::Distance::from_u32(point.count()).unwrap();
+                // distance_per_cluster[new_cluster] += new_distance * I::Distance::from_u64(point.count()).unwrap();
                 distance_per_cluster[new_cluster] = distance_per_cluster[new_cluster]
-                    + new_distance * I::Distance::from_u32(point.count()).unwrap();
+                    + new_distance * I::Distance::from_u64(point.count()).unwrap();
             }
         }
-        centers.push(new_center);
+        self.centers.push(new_center);
     }
 
-    let points_per_cluster = points_per_cluster(points, cluster_per_point, k);
+    pub fn points_per_cluster(&self) -> Vec<Vec<&'a I>> {
+        let k = self.centers.len() as u32;
+        points_per_cluster(self.points, self.cluster_per_point.clone(), k)
+    }
 
-    (centers, points_per_cluster)
+    pub fn into_clusters(self) -> (Vec<I::Output>, Vec<Vec<&'a I>>) {
+        let k = self.centers.len() as u32;
+        let points_per_cluster = points_per_cluster(self.points, self.cluster_per_point, k);
+        (self.centers, points_per_cluster)
+    }
+}
+
+/// The nearest center to each point, and the normalized distance to it.
+fn nearest_centers<I: Input>(
+    points: &[I],
+    centers: &[I::Output],
+) -> (Vec<usize>, Vec<I::Distance>) {
+    points
+        .iter()
+        .map(|point| {
+            let distances = centers
+                .iter()
+                .map(|center| point.normalized_distance(center));
+            let (cluster, distance) = distances
+                .enumerate()
+                .min_by_key(|&(_cluster, distance)| NotNan::new(distance).unwrap())
+                .unwrap();
+            (cluster, distance)
+        })
+        .unzip()
 }
 
 fn points_per_cluster<I: Input>(
@@ -91,7 +170,7 @@ fn farthest_point_of<T: Float>(
     target_cluster: usize,
     cluster_per_point: &[usize],
     distance_per_point: &[T],
-) -> usize {
+) -> Option<usize> {
     let point_indexes = cluster_per_point
         .iter()
         .zip(0..)
@@ -103,8 +182,7 @@ fn farthest_point_of<T: Float>(
             }
         });
     let distances_and_indexes = point_indexes.map(|i| (distance_per_point[i], i));
-    let (_distance, index) = distances_and_indexes
+    distances_and_indexes
         .max_by_key(|&(distance, _index)| NotNan::new(distance).unwrap())
-        .unwrap();
-    index
+        .map(|(_distance, index)| index)
 }
@@ -0,0 +1,103 @@
+//! Randomized initialization, used to get different starting points when
+//! restarting k-means.
+
+use super::super::Input;
+use super::{nearest_centers, points_per_cluster};
+use num::ToPrimitive;
+
+/// A small xorshift* generator, so that restarts are reproducible without
+/// pulling in a dependency.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        // The state must never be zero.
+        Random {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number in the range [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Chooses initial centers with k-means++, weighting each point by its count.
+///
+/// The fixed centers come first, and are used in place of a random first
+/// center if there are any.
+pub fn initialize_centers_randomly<'a, I: Input>(
+    k: u32,
+    points: &'a [I],
+    fixed_centers: &[I::Output],
+    seed: u64,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>) {
+    let mut random = Random::new(seed);
+    let mut centers = Vec::with_capacity(k as usize);
+    centers.extend_from_slice(fixed_centers);
+
+    let counts: Vec<_> = points.iter().map(|point| point.count() as f64).collect();
+    if centers.is_empty() {
+        let first_center = points[choose_weighted(&mut random, &counts)].as_output();
+        centers.push(first_center);
+    }
+
+    let (mut cluster_per_point, mut distance_per_point) = nearest_centers(points, &centers);
+
+    while centers.len() < (k as usize) {
+        let weights: Vec<_> = distance_per_point
+            .iter()
+            .zip(counts.iter())
+            .map(|(distance, &count)| distance.to_f64().unwrap() * count)
+            .collect();
+        let new_center = points[choose_weighted(&mut random, &weights)].as_output();
+
+        let new_cluster = centers.len();
+        for ((point, distance), cluster) in points
+            .iter()
+            .zip(distance_per_point.iter_mut())
+            .zip(cluster_per_point.iter_mut())
+        {
+            let new_distance = point.normalized_distance(&new_center);
+            if new_distance < *distance {
+                *cluster = new_cluster;
+                *distance = new_distance;
+            }
+        }
+        centers.push(new_center);
+    }
+
+    let points_per_cluster = points_per_cluster(points, cluster_per_point, centers.len() as u32);
+
+    (centers, points_per_cluster)
+}
+
+/// Picks an index with probability proportional to its weight.
+///
+/// If every weight is zero (all points already coincide with a center), any
+/// index is as good as another, so the choice is uniform.
+fn choose_weighted(random: &mut Random, weights: &[f64]) -> usize {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return (random.next_u64() % weights.len() as u64) as usize;
+    }
+
+    let mut target = random.next_f64() * total;
+    for (index, &weight) in weights.iter().enumerate() {
+        if target < weight {
+            return index;
+        }
+        target -= weight;
+    }
+    weights.len() - 1
+}
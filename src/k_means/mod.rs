@@ -13,12 +13,21 @@ use std::fmt::{Debug, Display};
 use std::hash::BuildHasherDefault;
 use std::hash::Hash;
 use std::iter::Sum;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 use num;
 use num::{Float, FromPrimitive, NumCast, Zero};
 use ordered_float::NotNan;
 
 mod initializer;
+mod outliers;
+mod refine;
+pub use self::outliers::protect_outliers;
+pub use self::refine::refine;
+
+#[cfg(test)]
+mod tests;
 
 /// This defines the functions k-means uses to cluster input data.
 pub trait Input: SimpleInput {
@@ -43,7 +52,7 @@ pub trait SimpleInput: Eq + Hash + Clone + Debug {
             .unwrap();
         cluster
     }
-    fn count(&self) -> u32 {
+    fn count(&self) -> u64 {
         1
     }
 }
@@ -60,7 +69,7 @@ pub trait Output: Eq + Hash + Clone + Debug {
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Grouped<I: SimpleInput> {
     pub data: I,
-    pub count: u32,
+    pub count: u64,
 }
 
 /// Groups identical input items together for efficiency.
@@ -69,11 +78,20 @@ where
     I: Iterator,
     I::Item: SimpleInput,
 {
-    let mut count_of_items: HashMap<I::Item, u32, BuildHasherDefault<DefaultHasher>> =
-        Default::default();
-    for item in items {
+    collect_weighted_groups(items.map(|item| (item, 1)))
+}
+
+/// Groups identical input items together like `collect_groups`, with each
+/// item counting as many times as its weight.
+pub fn collect_weighted_groups<I, T>(items: I) -> Vec<Grouped<T>>
+where
+    I: Iterator<Item = (T, u64)>,
+    T: SimpleInput,
+{
+    let mut count_of_items: HashMap<T, u64, BuildHasherDefault<DefaultHasher>> = Default::default();
+    for (item, weight) in items {
         let counter = count_of_items.entry(item).or_insert(0);
-        *counter += 1;
+        *counter += weight;
     }
 
     count_of_items
@@ -83,7 +101,7 @@ where
 }
 
 impl<I: SimpleInput> Grouped<I> {
-    fn new(data: I, count: u32) -> Grouped<I> {
+    fn new(data: I, count: u64) -> Grouped<I> {
         Grouped { data, count }
     }
 }
@@ -101,28 +119,284 @@ impl<I: SimpleInput> SimpleInput for Grouped<I> {
     fn as_output(&self) -> Self::Output {
         self.data.as_output()
     }
-    fn count(&self) -> u32 {
+    fn count(&self) -> u64 {
         self.count
     }
 }
 
 /// Run the k-means algorithm.
-pub fn run<I: Input>(data_points: &[I], k: u32, verbose: bool) -> (Vec<I::Output>, Vec<Vec<&I>>) {
-    let (mut centers, mut points_per_cluster) = initializer::initialize_centers(k, &data_points);
+///
+/// The fixed centers count toward k, but never move; they come first in the
+/// resulting centers.
+pub fn run<'a, I: Input>(
+    data_points: &'a [I],
+    k: u32,
+    fixed_centers: &[I::Output],
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>) {
+    let (centers, points_per_cluster) =
+        initializer::initialize_centers(k, &data_points, fixed_centers);
+    iterate(centers, points_per_cluster, fixed_centers.len(), verbose)
+}
+
+/// Run the k-means algorithm multiple times, keeping the result with the
+/// lowest total error.
+///
+/// The first run uses the same initialization as `run`; the rest use k-means++
+/// with a different seed each, so they converge to different local minima.
+/// Runs are spread across as many threads as the machine supports.
+pub fn run_with_restarts<'a, I>(
+    data_points: &'a [I],
+    k: u32,
+    fixed_centers: &[I::Output],
+    restarts: u32,
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>)
+where
+    I: Input + Sync,
+    I::Output: Send + Sync,
+    I::Distance: Send,
+{
+    if restarts <= 1 {
+        return run(data_points, k, fixed_centers, verbose);
+    }
+    best_of_restarts(data_points, k, fixed_centers, None, restarts, verbose)
+}
+
+/// Run the k-means algorithm `restarts` times in parallel and keep the result
+/// with the lowest total error. The first run starts from `first_centers`, or
+/// from the same initialization as `run` if there are none.
+fn best_of_restarts<'a, I>(
+    data_points: &'a [I],
+    k: u32,
+    fixed_centers: &[I::Output],
+    first_centers: Option<&[I::Output]>,
+    restarts: u32,
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>)
+where
+    I: Input + Sync,
+    I::Output: Send + Sync,
+    I::Distance: Send,
+{
+    let next_restart = AtomicUsize::new(0);
+    let num_threads = thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+        .min(restarts as usize);
+
+    let mut results = thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let restart = next_restart.fetch_add(1, Ordering::SeqCst);
+                        if restart >= restarts as usize {
+                            return results;
+                        }
+                        let (centers, points_per_cluster) = if restart > 0 {
+                            initializer::initialize_centers_randomly(
+                                k,
+                                data_points,
+                                fixed_centers,
+                                restart as u64,
+                            )
+                        } else if let Some(centers) = first_centers {
+                            (centers.to_vec(), assign_to_nearest(centers, data_points))
+                        } else {
+                            initializer::initialize_centers(k, data_points, fixed_centers)
+                        };
+                        let (centers, points_per_cluster) =
+                            iterate(centers, points_per_cluster, fixed_centers.len(), false);
+                        let error = total_distance(&centers, &points_per_cluster);
+                        results.push((restart, error, centers, points_per_cluster));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|&(restart, _, _, _)| restart);
+
+    if verbose {
+        for &(restart, error, _, _) in &results {
+            println!("Restart {}: total error {}", restart + 1, error);
+        }
+        let errors: Vec<_> = results.iter().map(|&(_, error, _, _)| error).collect();
+        let best = errors
+            .iter()
+            .cloned()
+            .fold(I::Distance::infinity(), Float::min);
+        let worst = errors.iter().cloned().fold(I::Distance::zero(), Float::max);
+        let mean =
+            errors.iter().cloned().sum::<I::Distance>() / I::Distance::from_u32(restarts).unwrap();
+        println!(
+            "Restart errors: best {}, worst {}, mean {}",
+            best, worst, mean
+        );
+    }
+
+    let (_restart, _error, centers, points_per_cluster) = results
+        .into_iter()
+        .min_by_key(|&(_, error, _, _)| NotNan::new(error).unwrap())
+        .unwrap();
+    (centers, points_per_cluster)
+}
+
+/// Run the k-means algorithm with the fewest clusters that satisfy
+/// `meets_target`, using at most `max_k` clusters.
+///
+/// Clusters are added one at a time with the same splitting the initializer
+/// uses, so growing k doesn't start over, until the initial clusters alone
+/// meet the target. The answer is then narrowed down with a binary search,
+/// running Lloyd iterations from the first k of those centers. With more than
+/// one restart, each k also tries that many k-means++ initializations and
+/// keeps the one with the lowest total error, as `run_with_restarts` does.
+pub fn run_to_target<'a, I, F>(
+    data_points: &'a [I],
+    max_k: u32,
+    fixed_centers: &[I::Output],
+    meets_target: F,
+    restarts: u32,
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>)
+where
+    I: Input + Sync,
+    I::Output: Send + Sync,
+    I::Distance: Send,
+    F: Fn(&[I::Output], &[Vec<&I>]) -> bool,
+{
+    let max_k = max_k as usize;
+    let num_fixed = fixed_centers.len();
+    let mut splitter = initializer::Splitter::new(data_points, fixed_centers);
+    while splitter.centers().len() < max_k
+        && !meets_target(splitter.centers(), &splitter.points_per_cluster())
+    {
+        splitter.add_center();
+    }
 
-    for iteration in 1.. {
+    let run_with_k = |splitter: &initializer::Splitter<I>, k: usize| {
+        let first_centers = &splitter.centers()[..k];
+        let result = if restarts <= 1 {
+            run_from_centers(data_points, first_centers.to_vec(), num_fixed)
+        } else {
+            best_of_restarts(
+                data_points,
+                k as u32,
+                fixed_centers,
+                Some(first_centers),
+                restarts,
+                false,
+            )
+        };
+        let meets = meets_target(&result.0, &result.1);
         if verbose {
-            println!("Iteration {:?}", iteration);
+            let verdict = if meets { "meets" } else { "misses" };
+            println!("{} colors {} the error target", k, verdict);
         }
+        (result, meets)
+    };
 
-        // Temp diagnostic message
-        {
-            let mut empty_clusters = 0;
-            for points in &points_per_cluster {
-                if points.is_empty() {
-                    empty_clusters += 1;
-                }
-            }
+    // Lloyd iterations lower the total distance, but not necessarily every
+    // error measure, so the upper bound may need to grow a bit further.
+    let mut high = splitter.centers().len();
+    let (mut best, mut meets) = run_with_k(&splitter, high);
+    while !meets && high < max_k {
+        splitter.add_center();
+        high += 1;
+        let (result, result_meets) = run_with_k(&splitter, high);
+        best = result;
+        meets = result_meets;
+    }
+    if !meets {
+        eprintln!(
+            "Warning: no palette of up to {} colors meets the error target; keeping {} colors.",
+            max_k,
+            best.0.len()
+        );
+        return best;
+    }
+
+    // The search assumes that once k clusters meet the target, so does every
+    // larger k. Since each k starts from the first k of the same split
+    // centers, more clusters only divide existing ones further, so the error
+    // rarely goes up; if it does, the result may be a little larger than the
+    // smallest k that meets the target, but it always meets it.
+    let mut low = num_fixed.max(1);
+    while low < high {
+        let middle = (low + high) / 2;
+        let (result, result_meets) = run_with_k(&splitter, middle);
+        if result_meets {
+            high = middle;
+            best = result;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    best
+}
+
+/// Run Lloyd iterations starting from the given centers.
+fn run_from_centers<I: Input>(
+    data_points: &[I],
+    centers: Vec<I::Output>,
+    num_fixed: usize,
+) -> (Vec<I::Output>, Vec<Vec<&I>>) {
+    let points_per_cluster = assign_to_nearest(&centers, data_points);
+    iterate(centers, points_per_cluster, num_fixed, false)
+}
+
+/// The total distance from each point to its cluster's center, weighted by
+/// the point's count.
+pub fn total_distance<I: Input>(
+    centers: &[I::Output],
+    points_per_cluster: &[Vec<&I>],
+) -> I::Distance {
+    centers
+        .iter()
+        .zip(points_per_cluster.iter())
+        .map(|(center, points)| {
+            points
+                .iter()
+                .map(|point| {
+                    point.distance_to(center) * I::Distance::from_u64(point.count()).unwrap()
+                })
+                .sum()
+        })
+        .sum()
+}
+
+/// The most Lloyd iterations to run. The clusters usually settle within a few
+/// dozen, but when the mean doesn't minimize the distance to a cluster's
+/// points, as with colors compared without regard to alpha, they can cycle
+/// instead.
+const MAX_ITERATIONS: u32 = 1000;
+
+/// Run Lloyd iterations from the given starting clusters until they converge,
+/// or for at most `MAX_ITERATIONS`.
+///
+/// The first `num_fixed` centers stay where they are.
+fn iterate<I: Input>(
+    mut centers: Vec<I::Output>,
+    mut points_per_cluster: Vec<Vec<&I>>,
+    num_fixed: usize,
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&I>>) {
+    for iteration in 1..=MAX_ITERATIONS {
+        if verbose {
+            println!("Iteration {:?}", iteration);
+
+            // Fixed colors are allowed to go unused.
+            let empty_clusters = points_per_cluster[num_fixed..]
+                .iter()
+                .filter(|points| points.is_empty())
+                .count();
             match empty_clusters {
                 0 => (),
                 1 => println!("1 empty cluster found."),
@@ -131,7 +405,7 @@ pub fn run<I: Input>(data_points: &[I], k: u32, verbose: bool) -> (Vec<I::Output
         }
 
         let prior_points_per_cluster = points_per_cluster.clone();
-        reposition_centers(&mut centers, &points_per_cluster);
+        reposition_centers(&mut centers, &points_per_cluster, num_fixed);
         points_per_cluster = assign_to_clusters(&centers, &points_per_cluster);
 
         if points_per_cluster == prior_points_per_cluster {
@@ -213,8 +487,27 @@ fn calculate_distances_between_centers<O: Output>(centers: &[O]) -> Vec<Vec<(u32
     distances_per_center
 }
 
-fn reposition_centers<I: Input>(centers: &mut Vec<I::Output>, points_per_cluster: &[Vec<&I>]) {
-    for (center, points) in centers.iter_mut().zip(points_per_cluster.iter()) {
-        *center = I::mean_of(points);
+fn reposition_centers<I: Input>(
+    centers: &mut [I::Output],
+    points_per_cluster: &[Vec<&I>],
+    num_fixed: usize,
+) {
+    for (center, points) in centers
+        .iter_mut()
+        .zip(points_per_cluster.iter())
+        .skip(num_fixed)
+    {
+        // An empty cluster has no mean; leave its center where it is.
+        if !points.is_empty() {
+            *center = I::mean_of(points);
+        }
     }
 }
+
+fn assign_to_nearest<'a, I: Input>(centers: &[I::Output], points: &'a [I]) -> Vec<Vec<&'a I>> {
+    let mut points_per_cluster = vec![Vec::new(); centers.len()];
+    for point in points {
+        points_per_cluster[point.nearest(centers) as usize].push(point);
+    }
+    points_per_cluster
+}
@@ -0,0 +1,91 @@
+//! Protection for points far from every center.
+//!
+//! Clusters are weighted by how many points they hold, so a few points far
+//! from the rest tend to be averaged into a large cluster nearby. Here each
+//! such point gets a fixed center of its own, taking a center from the
+//! cluster that is cheapest to lose once every center is in use.
+
+use super::refine::cheapest_removals;
+use super::{assign_to_nearest, iterate, Input};
+
+/// Gives each point farther than `threshold` from every center, as measured
+/// by `distance`, a fixed center of its own, farthest first, until no such
+/// point is left or only one center is left free to move.
+///
+/// Centers are added while there are fewer than `max_k`, and after that each
+/// replaces the free center that is cheapest to lose. Protected centers join
+/// the first `num_fixed` centers, which never move, and the free centers then
+/// settle around them with Lloyd iterations, which may leave more points to
+/// protect. Returns the centers, their clusters and the new number of fixed
+/// centers.
+pub fn protect_outliers<'a, I, F>(
+    data_points: &'a [I],
+    centers: Vec<I::Output>,
+    points_per_cluster: Vec<Vec<&'a I>>,
+    num_fixed: usize,
+    max_k: usize,
+    threshold: f64,
+    distance: F,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>, usize)
+where
+    I: Input,
+    F: Fn(&I, &I::Output) -> f64,
+{
+    let mut centers = centers;
+    let mut points_per_cluster = points_per_cluster;
+    let mut num_fixed = num_fixed;
+
+    loop {
+        let mut protected = 0;
+        while let Some(outlier) = farthest_outlier(data_points, &centers, threshold, &distance) {
+            if centers.len() >= max_k {
+                if centers.len() - num_fixed <= 1 {
+                    break;
+                }
+                let removal = cheapest_removals(&centers, &points_per_cluster, num_fixed)[0];
+                centers.remove(removal);
+            }
+            centers.insert(num_fixed, outlier.as_output());
+            num_fixed += 1;
+            points_per_cluster = assign_to_nearest(&centers, data_points);
+            protected += 1;
+        }
+        if protected == 0 {
+            return (centers, points_per_cluster, num_fixed);
+        }
+
+        let (settled_centers, settled_points_per_cluster) =
+            iterate(centers, points_per_cluster, num_fixed, false);
+        centers = settled_centers;
+        points_per_cluster = settled_points_per_cluster;
+    }
+}
+
+/// The point farthest from every center, if it's farther than the threshold
+/// and doesn't have a center of its own already.
+fn farthest_outlier<'a, I, F>(
+    data_points: &'a [I],
+    centers: &[I::Output],
+    threshold: f64,
+    distance: &F,
+) -> Option<&'a I>
+where
+    I: Input,
+    F: Fn(&I, &I::Output) -> f64,
+{
+    let mut farthest = None;
+    let mut farthest_distance = threshold;
+    for point in data_points {
+        let nearest_distance = centers
+            .iter()
+            .map(|center| distance(point, center))
+            .fold(f64::INFINITY, f64::min);
+        // A center may not match its point exactly, as when the output can't
+        // store every input color, and protecting it again wouldn't help.
+        if nearest_distance > farthest_distance && !centers.contains(&point.as_output()) {
+            farthest = Some(point);
+            farthest_distance = nearest_distance;
+        }
+    }
+    farthest
+}
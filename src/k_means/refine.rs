@@ -0,0 +1,156 @@
+//! Local search to improve on a converged k-means result.
+//!
+//! Lloyd iterations stop at a local minimum, where moving any single center
+//! to the mean of its points can't help, but moving a center somewhere else
+//! entirely still might. Each move here merges away the cluster that is
+//! cheapest to lose and splits the worst cluster by putting a center on one
+//! of its farthest points, then lets Lloyd iterations settle again.
+
+use super::{assign_to_nearest, iterate, total_distance, Input};
+use num::{Float, FromPrimitive};
+use ordered_float::NotNan;
+
+/// How many clusters to consider removing, and how many points to consider
+/// adding, in each round of moves.
+const CANDIDATES: usize = 4;
+
+/// Refine the clusters with center swaps, until no move lowers the total
+/// error or `budget` moves have been tried.
+///
+/// The first `num_fixed` centers are never moved, as from `run` with fixed
+/// centers.
+pub fn refine<'a, I: Input>(
+    data_points: &'a [I],
+    centers: Vec<I::Output>,
+    points_per_cluster: Vec<Vec<&'a I>>,
+    num_fixed: usize,
+    budget: u32,
+    verbose: bool,
+) -> (Vec<I::Output>, Vec<Vec<&'a I>>) {
+    let mut centers = centers;
+    let mut points_per_cluster = points_per_cluster;
+    let mut error = total_distance(&centers, &points_per_cluster);
+    let mut moves_tried = 0;
+
+    'rounds: while moves_tried < budget {
+        let removals = cheapest_removals(&centers, &points_per_cluster, num_fixed);
+        let additions = farthest_points(&centers, &points_per_cluster);
+
+        for &removal in &removals {
+            for &addition in &additions {
+                if moves_tried >= budget {
+                    break 'rounds;
+                }
+                moves_tried += 1;
+
+                let mut new_centers = centers.clone();
+                new_centers[removal] = addition.as_output();
+                let new_points_per_cluster = assign_to_nearest(&new_centers, data_points);
+                if new_points_per_cluster[num_fixed..]
+                    .iter()
+                    .any(Vec::is_empty)
+                {
+                    continue;
+                }
+
+                let (new_centers, new_points_per_cluster) =
+                    iterate(new_centers, new_points_per_cluster, num_fixed, false);
+                let new_error = total_distance(&new_centers, &new_points_per_cluster);
+                if new_error < error {
+                    if verbose {
+                        println!(
+                            "Refinement move {}: total error {} -> {}",
+                            moves_tried, error, new_error
+                        );
+                    }
+                    centers = new_centers;
+                    points_per_cluster = new_points_per_cluster;
+                    error = new_error;
+                    continue 'rounds;
+                }
+            }
+        }
+
+        // No move helped.
+        break;
+    }
+
+    if verbose {
+        println!(
+            "Refinement finished after {} moves, total error {}",
+            moves_tried, error
+        );
+    }
+
+    (centers, points_per_cluster)
+}
+
+/// The clusters whose removal would increase the total error the least, if
+/// their points moved to their next nearest centers.
+pub fn cheapest_removals<I: Input>(
+    centers: &[I::Output],
+    points_per_cluster: &[Vec<&I>],
+    num_fixed: usize,
+) -> Vec<usize> {
+    let mut costs: Vec<_> = points_per_cluster
+        .iter()
+        .enumerate()
+        .skip(num_fixed)
+        .map(|(cluster, points)| {
+            let cost: I::Distance = points
+                .iter()
+                .map(|point| {
+                    let next_nearest = centers
+                        .iter()
+                        .enumerate()
+                        .filter(|&(other, _)| other != cluster)
+                        .map(|(_, center)| NotNan::new(point.distance_to(center)).unwrap())
+                        .min()
+                        .map_or(I::Distance::infinity(), NotNan::into_inner);
+                    let increase = next_nearest - point.distance_to(&centers[cluster]);
+                    increase * I::Distance::from_u64(point.count()).unwrap()
+                })
+                .sum();
+            (NotNan::new(cost).unwrap(), cluster)
+        })
+        .collect();
+    costs.sort();
+    costs
+        .into_iter()
+        .take(CANDIDATES)
+        .map(|(_cost, cluster)| cluster)
+        .collect()
+}
+
+/// The points contributing the most to the total error, skipping any that
+/// are already represented exactly by a center.
+fn farthest_points<'a, I: Input>(
+    centers: &[I::Output],
+    points_per_cluster: &[Vec<&'a I>],
+) -> Vec<&'a I> {
+    let mut points_with_errors: Vec<_> = centers
+        .iter()
+        .zip(points_per_cluster.iter())
+        .flat_map(|(center, points)| {
+            points.iter().map(move |&point| {
+                let error =
+                    point.distance_to(center) * I::Distance::from_u64(point.count()).unwrap();
+                (NotNan::new(error).unwrap(), point)
+            })
+        })
+        .collect();
+    points_with_errors.sort_by_key(|&(error, _point)| ::std::cmp::Reverse(error));
+
+    let mut farthest: Vec<&I> = Vec::with_capacity(CANDIDATES);
+    for (_error, point) in points_with_errors {
+        if farthest.len() == CANDIDATES {
+            break;
+        }
+        let output = point.as_output();
+        if centers.contains(&output) || farthest.iter().any(|other| other.as_output() == output) {
+            continue;
+        }
+        farthest.push(point);
+    }
+    farthest
+}
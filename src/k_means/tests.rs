@@ -0,0 +1,189 @@
+use super::*;
+use color::{Color, ConvertibleColor, Pixel, Rgba8};
+
+fn test_points() -> Vec<Grouped<ConvertibleColor<Rgba8, Rgba8>>> {
+    let mut pixels = Vec::new();
+    for r in 0..8 {
+        for g in 0..8 {
+            pixels.push(Pixel {
+                data: [r * 32, g * 32, (r * g) as u8 * 4, 0xFF],
+            });
+        }
+    }
+    collect_groups(pixels.into_iter().map(ConvertibleColor::from))
+}
+
+#[test]
+fn restarts_are_no_worse_than_a_single_run() {
+    let points = test_points();
+    let (centers, points_per_cluster) = run(&points, 5, &[], false);
+    let single_run_error = total_distance(&centers, &points_per_cluster);
+
+    let (centers, points_per_cluster) = run_with_restarts(&points, 5, &[], 4, false);
+    let restarts_error = total_distance(&centers, &points_per_cluster);
+
+    assert!(restarts_error <= single_run_error);
+}
+
+#[test]
+fn random_initialization_is_reproducible() {
+    let points = test_points();
+    let (first_centers, _) = initializer::initialize_centers_randomly(5, &points, &[], 3);
+    let (second_centers, _) = initializer::initialize_centers_randomly(5, &points, &[], 3);
+    assert_eq!(first_centers, second_centers);
+}
+
+#[test]
+fn refinement_never_increases_error() {
+    let points = test_points();
+    let (centers, points_per_cluster) = run(&points, 5, &[], false);
+    let error = total_distance(&centers, &points_per_cluster);
+
+    let (centers, points_per_cluster) = refine(&points, centers, points_per_cluster, 0, 20, false);
+    let refined_error = total_distance(&centers, &points_per_cluster);
+
+    assert!(refined_error <= error);
+    assert_eq!(centers.len(), 5);
+}
+
+#[test]
+fn run_to_target_finds_the_fewest_clusters() {
+    let pixels = [
+        [0xFF, 0x00, 0x00, 0xFF],
+        [0x00, 0xFF, 0x00, 0xFF],
+        [0x00, 0x00, 0xFF, 0xFF],
+    ];
+    let points = collect_groups(
+        pixels
+            .iter()
+            .cycle()
+            .take(30)
+            .map(|&data| ConvertibleColor::<Rgba8, Rgba8>::from(Pixel { data })),
+    );
+
+    let (centers, _) = run_to_target(
+        &points,
+        16,
+        &[],
+        |centers, points_per_cluster| total_distance(centers, points_per_cluster) == 0.0,
+        1,
+        false,
+    );
+    assert_eq!(centers.len(), 3);
+}
+
+#[test]
+fn error_decreases_as_split_centers_are_added() {
+    let points = test_points();
+    let mut splitter = initializer::Splitter::new(&points, &[]);
+    let mut errors = Vec::new();
+    for k in 1..=12 {
+        while splitter.centers().len() < k {
+            splitter.add_center();
+        }
+        let (centers, points_per_cluster) =
+            run_from_centers(&points, splitter.centers().to_vec(), 0);
+        errors.push(total_distance(&centers, &points_per_cluster));
+    }
+    assert!(errors.windows(2).all(|pair| pair[1] <= pair[0]));
+
+    for &target in &errors {
+        let (centers, _) = run_to_target(
+            &points,
+            16,
+            &[],
+            |centers, points_per_cluster| total_distance(centers, points_per_cluster) <= target,
+            1,
+            false,
+        );
+        let fewest = errors.iter().position(|&error| error <= target).unwrap();
+        assert_eq!(centers.len(), fewest + 1);
+    }
+}
+
+#[test]
+fn fixed_centers_never_move() {
+    let points = test_points();
+    let fixed: Vec<Rgba8> = [[0xFF, 0xFF, 0xFF, 0xFF], [0xC0, 0x10, 0x20, 0xFF]]
+        .iter()
+        .map(|&data| Pixel { data }.into())
+        .collect();
+
+    let (centers, points_per_cluster) = run(&points, 5, &fixed, false);
+    assert_eq!(centers.len(), 5);
+    assert_eq!(&centers[..2], &fixed[..]);
+
+    let (centers, _) = run_with_restarts(&points, 5, &fixed, 3, false);
+    assert_eq!(&centers[..2], &fixed[..]);
+
+    let (centers, _) = refine(&points, centers.clone(), points_per_cluster, 2, 20, false);
+    assert_eq!(&centers[..2], &fixed[..]);
+
+    let (centers, _) = run_to_target(&points, 16, &fixed, |_, _| true, 3, false);
+    assert_eq!(&centers[..], &fixed[..]);
+}
+
+#[test]
+fn weighted_groups_count_each_item_by_its_weight() {
+    let black = ConvertibleColor::<Rgba8, Rgba8>::from(Pixel {
+        data: [0x00, 0x00, 0x00, 0xFF],
+    });
+    let white = ConvertibleColor::<Rgba8, Rgba8>::from(Pixel {
+        data: [0xFF, 0xFF, 0xFF, 0xFF],
+    });
+    let groups = collect_weighted_groups(vec![(black, 3), (white, 1), (black, 1)].into_iter());
+    let black_count = groups
+        .iter()
+        .find(|group| group.data == black)
+        .map(|group| group.count);
+    assert_eq!(black_count, Some(4));
+
+    let groups: Vec<_> = groups.iter().collect();
+    let mean = Input::mean_of(&groups).as_pixel();
+    assert_eq!(mean[0], 0x33);
+}
+
+#[test]
+fn outliers_get_centers_of_their_own() {
+    let grays = (0..8).map(|step| {
+        let value = 0x70 + step * 4;
+        (
+            Pixel {
+                data: [value, value, value, 0xFF],
+            },
+            100,
+        )
+    });
+    let outliers = [
+        [0xFF, 0x00, 0x00, 0xFF],
+        [0x00, 0xFF, 0x00, 0xFF],
+        [0x00, 0x00, 0xFF, 0xFF],
+    ];
+    let points = collect_weighted_groups(
+        grays
+            .chain(outliers.iter().map(|&data| (Pixel { data }, 1)))
+            .map(|(pixel, count)| (ConvertibleColor::<Rgba8, Rgba8>::from(pixel), count)),
+    );
+    let centers: Vec<_> = points
+        .iter()
+        .filter(|point| point.count == 100)
+        .take(4)
+        .map(|point| point.as_output())
+        .collect();
+    let points_per_cluster = assign_to_nearest(&centers, &points);
+
+    let (centers, _, num_fixed) = protect_outliers(
+        &points,
+        centers,
+        points_per_cluster,
+        0,
+        4,
+        0.1,
+        |point, center| point.distance_to(center),
+    );
+    assert_eq!(centers.len(), 4);
+    assert_eq!(num_fixed, 3);
+    for &data in &outliers {
+        assert!(centers[..num_fixed].contains(&Rgba8::from(Pixel { data })));
+    }
+}
@@ -5,6 +5,7 @@
 #![cfg_attr(test, feature(test))]
 
 use std::env;
+use std::fmt::Display;
 use std::path::{Path, PathBuf};
 
 extern crate image as image_lib;
@@ -17,8 +18,10 @@ use getopts::{Matches, Options};
 
 mod color;
 mod images;
+mod json;
 mod k_means;
 mod options;
+mod palette;
 
 #[cfg(test)]
 extern crate test;
@@ -35,7 +38,7 @@ fn main() {
     };
 
     if matches.opt_present("help") {
-        print_usage(program, options);
+        print!("{}", usage(program, &options));
         return;
     }
 
@@ -44,49 +47,58 @@ fn main() {
         return;
     }
 
-    let colortype = options::color_type(matches.opt_str("colortype")).unwrap_or_else(|error| {
-        println!("{}", error);
-        std::process::exit(1);
-    });
-
-    let num_colors: u32 = matches
-        .opt_get_default("colors", 256)
-        .unwrap_or_else(|error| {
-            println!("{}", error);
-            std::process::exit(1);
-        });
-
-    if num_colors > 256 {
-        println!("More than 256 colors in the palette is not supported.");
-        std::process::exit(1);
-    }
-
     if matches.free.is_empty() {
         exit_with_bad_args("No input file specified.", program, options);
     }
 
-    let verbose = matches.opt_present("verbose");
-
     let input_paths: Vec<&Path> = matches
         .free
         .iter()
         .map(|input_string| Path::new(input_string))
         .collect();
-    let output_pathbufs: Vec<PathBuf> = input_paths
-        .iter()
-        .map(|input_path| get_output_path(input_path, &matches))
-        .collect();
-    let result = images::quantize(
-        input_paths.into_iter(),
-        output_pathbufs.iter().map(PathBuf::as_path),
-        colortype,
-        num_colors,
-        verbose,
-    );
+
+    let settings = match options::settings(&matches, &input_paths) {
+        Ok(settings) => settings,
+        Err(options::SettingsError::Usage(error)) => exit_with_bad_args(&error, program, options),
+        Err(options::SettingsError::Invalid(error)) => exit_with_error(error),
+    };
+
+    let result = match matches.opt_str("compose") {
+        Some(index_path) => {
+            let output_pathbufs: Vec<PathBuf> = input_paths
+                .iter()
+                .map(|input_path| get_output_path(input_path, &matches, " (composed)"))
+                .collect();
+            images::compose(
+                Path::new(&index_path),
+                input_paths.into_iter(),
+                output_pathbufs.iter().map(PathBuf::as_path),
+                &settings,
+            )
+        }
+        None => {
+            let output_pathbufs: Vec<PathBuf> = input_paths
+                .iter()
+                .map(|input_path| get_output_path(input_path, &matches, " (smashed)"))
+                .collect();
+            match matches.opt_str("add-to") {
+                Some(index_path) => images::add_to_indexes(
+                    Path::new(&index_path),
+                    input_paths.into_iter(),
+                    output_pathbufs.iter().map(PathBuf::as_path),
+                    &settings,
+                ),
+                None => images::quantize(
+                    input_paths.into_iter(),
+                    output_pathbufs.iter().map(PathBuf::as_path),
+                    &settings,
+                ),
+            }
+        }
+    };
 
     if let Err(error) = result {
-        println!("{}", error);
-        std::process::exit(1);
+        exit_with_error(error);
     }
 }
 
@@ -105,35 +117,264 @@ fn initialize_options() -> Options {
     options.optopt(
         "c",
         "colortype",
-        "set output to RGBA8 (default) or RGB5A3.",
+        "set output to RGBA8 (default), RGB5A3, RGB565, RGBA4444, ARGB1555, IA8, IA4, BGR555, \
+         ABGR1555, or a bit layout such as R5G6B5 or A3R4G4B4|R5G5B5.",
         "TYPE",
     );
+    options.optopt(
+        "",
+        "formats",
+        "read named bit layouts to use as color types from FILE, one NAME = LAYOUT per line.",
+        "FILE",
+    );
+    options.optopt(
+        "",
+        "master",
+        "only use colors from the NES, PICO-8 or GAMEBOY master palette, or one read from FILE.",
+        "PALETTE",
+    );
     options.optopt(
         "n",
         "colors",
-        "set number of colors in output files.",
+        "set number of colors in output files (the maximum, with --target-error).",
+        "NUMBER",
+    );
+    options.optopt(
+        "r",
+        "restarts",
+        "run k-means NUMBER times from different starting points and keep the best result.",
+        "NUMBER",
+    );
+    options.optopt(
+        "",
+        "refine",
+        "after k-means, try up to MOVES center swaps to lower the error further.",
+        "MOVES",
+    );
+    options.optopt(
+        "",
+        "target-error",
+        "use the fewest colors with error within TARGET: mean:VALUE, max:VALUE or psnr:DECIBELS.",
+        "TARGET",
+    );
+    options.optopt(
+        "",
+        "protect-outliers",
+        "give colors farther than DISTANCE from every palette color their own, while there's \
+         room; measured like max error.",
+        "DISTANCE",
+    );
+    options.optopt(
+        "",
+        "matte",
+        "flatten transparent pixels against COLOR (as RRGGBB hex).",
+        "COLOR",
+    );
+    options.optopt(
+        "",
+        "alpha",
+        "handle transparency with MODE: keep (default), threshold to fully transparent or \
+         opaque, premultiplied to compare colors scaled by alpha, or matte (with --matte).",
+        "MODE",
+    );
+    options.optopt(
+        "",
+        "alpha-weight",
+        "count differences in alpha WEIGHT times as much as in a color component \
+         (default 3).",
+        "WEIGHT",
+    );
+    options.optopt(
+        "",
+        "weights",
+        "how much each image counts, as comma-separated numbers in the order of the input \
+         files, or a FILE with a PATH = WEIGHT on each line (others count 1).",
+        "WEIGHTS",
+    );
+    options.optmulti(
+        "",
+        "mask",
+        "weight each pixel by the brightness of the grayscale image FILE, one for all images \
+         or one for each; can be repeated.",
+        "FILE",
+    );
+    options.optopt(
+        "",
+        "edge-weight",
+        "count pixels on edges more, up to 1 + STRENGTH times as much at the strongest.",
+        "STRENGTH",
+    );
+    options.optopt(
+        "",
+        "flat-weight",
+        "count pixels in flat regions of a single color FACTOR times as much.",
+        "FACTOR",
+    );
+    options.optmulti(
+        "",
+        "lock",
+        "keep COLORS (as RRGGBB or RRGGBBAA hex) in the palette, one for all images or \
+         comma-separated for each; can be repeated.",
+        "COLORS",
+    );
+    options.optopt(
+        "",
+        "luma",
+        "convert color to intensity for IA8, IA4 and I layouts with Rec. 601 (default) \
+         or 709.",
+        "STANDARD",
+    );
+    options.optflag(
+        "",
+        "transparent-index",
+        "keep palette index 0 for fully transparent pixels.",
+    );
+    options.optflag(
+        "",
+        "report",
+        "print error, PSNR and SSIM for each output image.",
+    );
+    options.optopt(
+        "",
+        "report-json",
+        "write the quality report to FILE as JSON.",
+        "FILE",
+    );
+    options.optflag(
+        "",
+        "heatmap",
+        "also write an image of the error at each pixel for each output file.",
+    );
+    options.optflag(
+        "",
+        "composite",
+        "also write the input, output and error heatmap side by side for each output file.",
+    );
+    options.optflag(
+        "",
+        "raw-palette",
+        "also write each palette in the color type's native format, as FILE.palette.bin.",
+    );
+    options.optflag(
+        "",
+        "raw",
+        "write raw binary files instead of PNGs: the indexes once, as FILE.indexes.bin for \
+         the first file, and each palette in the color type's native format as \
+         FILE.palette.bin.",
+    );
+    options.optopt(
+        "",
+        "index-bits",
+        "store raw indexes in 8 (default) or 4 bits, packing two pixels into each byte.",
+        "BITS",
+    );
+    options.optflag(
+        "",
+        "low-nibble-first",
+        "put the first of each pair of 4 bit indexes in the low nibble.",
+    );
+    options.optopt(
+        "",
+        "row-align",
+        "pad each row of raw indexes to a multiple of BYTES.",
+        "BYTES",
+    );
+    options.optflag(
+        "",
+        "raw-header",
+        "start each raw file with a header giving its size and layout.",
+    );
+    options.optopt(
+        "",
+        "add-to",
+        "instead of finding new indexes, fit a palette for each input file to the existing \
+         indexes in FILE (as read by --compose), and report the error.",
+        "FILE",
+    );
+    options.optopt(
+        "",
+        "compose",
+        "instead of quantizing, rebuild a full color image from the indexes in FILE (an \
+         8 bit indexed or grayscale PNG, or raw indexes with a header) for each palette \
+         given in place of input files.",
+        "FILE",
+    );
+    options.optflag(
+        "",
+        "bundle",
+        "store the indexes once instead of in every output file: write them as a grayscale \
+         FILE.indexes.png for the first file, each palette as FILE.palette.hex, and a \
+         FILE.manifest.json listing which palette goes with which image.",
+    );
+    options.optmulti(
+        "",
+        "export-source",
+        "also write the indexes and native palettes as source code in LANGUAGE, c or rust, \
+         as FILE.h or FILE.rs for the first file; can be repeated.",
+        "LANGUAGE",
+    );
+    options.optopt(
+        "",
+        "symbol-prefix",
+        "start the names in exported source code with PREFIX.",
+        "PREFIX",
+    );
+    options.optopt(
+        "",
+        "values-per-line",
+        "put NUMBER values on each line of exported source code (default 16).",
         "NUMBER",
     );
+    options.optmulti(
+        "",
+        "export-palette",
+        "also write each palette as FILE.palette.EXT in FORMAT: act (Photoshop), gpl (GIMP), \
+         pal (JASC) or hex; can be repeated.",
+        "FORMAT",
+    );
+    options.optopt(
+        "",
+        "combined-palette",
+        "write every image's palette, one after another, to FILE (.act, .gpl, .pal or .hex).",
+        "FILE",
+    );
+    options.optmulti(
+        "",
+        "remap",
+        "map the images onto the colors of PALETTE instead of finding new ones, keeping \
+         its order; an indexed PNG, .act, .gpl, .pal, .hex or a raw palette (.bin). Give one \
+         for all images or repeat it for each.",
+        "PALETTE",
+    );
+    options.optflag(
+        "",
+        "dither",
+        "dither with Floyd-Steinberg when using --remap.",
+    );
 
     options
 }
 
-fn print_usage(program: &str, options: Options) {
+fn usage(program: &str, options: &Options) -> String {
     let brief = format!("Usage: {} [options] FILE", program);
-    print!("{}", options.usage(&brief));
+    options.usage(&brief)
 }
 
 fn exit_with_bad_args(error: &str, program: &str, options: Options) -> ! {
-    print!("{}\n\n", error);
-    print_usage(program, options);
+    print!("{}\n\n{}", error, usage(program, &options));
+    std::process::exit(1);
+}
+
+fn exit_with_error<E: Display>(error: E) -> ! {
+    println!("{}", error);
     std::process::exit(1);
 }
 
-fn get_output_path(input_file: &Path, matches: &Matches) -> PathBuf {
+fn get_output_path(input_file: &Path, matches: &Matches, default_suffix: &str) -> PathBuf {
     let stem = input_file.file_stem().unwrap();
     let output_suffix = match matches.opt_str("suffix") {
         Some(suffix) => suffix,
-        None => " (smashed)".to_string(),
+        None => default_suffix.to_string(),
     };
     let output_extension = ".png";
     let output_name = stem.to_string_lossy().into_owned() + &output_suffix + output_extension;
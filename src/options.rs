@@ -1,26 +1,843 @@
 //! Defines options that are passed to images::quantize.
 //!
-//! Option enums belong here, and helper functions that deal with them, but
-//! command-line option handling is still in main. This module should be useful
-//! for alternate interfaces as well.
+//! Option enums belong here, and helper functions that deal with them, along
+//! with building Settings from the command-line options, which main declares.
+//! Apart from `settings`, this module should be useful for alternate
+//! interfaces as well.
 
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use getopts::Matches;
+
+use color::{Format, MasterPalette, Pixel};
+use palette;
+
+/// Everything images::quantize needs to know besides the file paths.
+pub struct Settings {
+    pub colortype: ColorType,
+    /// The number of colors, or the maximum number when there's an error target.
+    pub num_colors: u32,
+    pub restarts: u32,
+    pub refine_moves: u32,
+    pub error_target: Option<ErrorTarget>,
+    /// Give color combinations farther than this from every center their
+    /// own palette entry while there's room, measured like the max error.
+    pub outlier_threshold: Option<f64>,
+    /// How transparency is handled. Flattening against a matte is required
+    /// for images with transparency when the color type has no alpha.
+    pub alpha: AlphaMode,
+    /// How much a difference in alpha counts compared to one in a color
+    /// component.
+    pub alpha_weight: f64,
+    /// How much each image's error counts when clustering, one for each
+    /// image, or empty for every image to count the same.
+    pub weights: Vec<f64>,
+    /// Grayscale images whose brightness at each pixel multiplies how much
+    /// it counts when clustering, one for all of the images or one for each.
+    pub masks: Vec<PathBuf>,
+    /// How much more pixels count when clustering at the strongest edges,
+    /// scaling with edge strength, or `None` to ignore edges.
+    pub edge_weight: Option<f64>,
+    /// How much pixels in flat regions, where every neighbor is the same
+    /// color, count when clustering, or `None` to count them as usual.
+    pub flat_weight: Option<f64>,
+    /// Color combinations that must be in the palette, which count toward
+    /// the number of colors. A single color stands for the same color in
+    /// every image.
+    pub locked: Vec<Vec<Pixel>>,
+    /// How intensity is calculated for the grayscale color types.
+    pub luma: Luma,
+    /// Print a quality report comparing the output to the input.
+    pub report: bool,
+    /// Write the quality report as JSON to this path.
+    pub report_json: Option<PathBuf>,
+    /// Write an image of the error at each pixel next to each output image.
+    pub heatmap: bool,
+    /// Write the original, output and heatmap side by side next to each
+    /// output image.
+    pub composite: bool,
+    /// Write each palette in the color type's native format next to each
+    /// output image.
+    pub raw_palette: bool,
+    /// Palettes to map the images onto instead of clustering, one for all
+    /// of the images or one for each.
+    pub remap: Vec<PathBuf>,
+    /// Dither when mapping onto an existing palette.
+    pub dither: bool,
+    /// Keep palette index 0 for fully transparent pixels, whatever the
+    /// color type.
+    pub transparent_index: bool,
+    /// Write the indexes and native palettes as raw binary files instead of
+    /// PNGs, laid out like this.
+    pub raw: Option<RawLayout>,
+    /// Write one index image, a palette for each image and a manifest
+    /// instead of an indexed PNG for each image.
+    pub bundle: bool,
+    /// Languages to write the indexes and palettes as source code in.
+    pub source_languages: Vec<SourceLanguage>,
+    /// Starts the name of everything in the source code.
+    pub symbol_prefix: String,
+    /// The number of values on each line of the source code.
+    pub values_per_line: usize,
+    /// Formats to write each image's palette in, next to each output image.
+    pub palette_formats: Vec<PaletteFormat>,
+    /// Write every image's palette, one after another, to this path in this
+    /// format.
+    pub combined_palette: Option<(PathBuf, PaletteFormat)>,
+    pub verbose: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            colortype: ColorType::Rgba8,
+            num_colors: 256,
+            restarts: 1,
+            refine_moves: 0,
+            error_target: None,
+            outlier_threshold: None,
+            alpha: AlphaMode::Keep,
+            alpha_weight: 3.0,
+            weights: Vec::new(),
+            masks: Vec::new(),
+            edge_weight: None,
+            flat_weight: None,
+            locked: Vec::new(),
+            luma: Luma::Rec601,
+            report: false,
+            report_json: None,
+            heatmap: false,
+            composite: false,
+            raw_palette: false,
+            remap: Vec::new(),
+            dither: false,
+            transparent_index: false,
+            raw: None,
+            bundle: false,
+            source_languages: Vec::new(),
+            symbol_prefix: String::new(),
+            values_per_line: 16,
+            palette_formats: Vec::new(),
+            combined_palette: None,
+            verbose: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Whether palette index 0 holds full transparency, either because it
+    /// was asked for or because the color type always does.
+    pub fn reserves_transparent_index(&self) -> bool {
+        self.transparent_index || self.colortype.reserves_transparent_index()
+    }
+}
+
+/// Parses a numeric option, or gives the default if it's missing.
+fn number<T>(matches: &Matches, name: &str, default: T) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    matches
+        .opt_get_default(name, default)
+        .map_err(|error| format!("Invalid --{}: {}", name, error))
+}
+
+/// Why the command-line options couldn't be turned into Settings.
+#[derive(Debug, PartialEq)]
+pub enum SettingsError {
+    /// An option's value couldn't be parsed or read.
+    Invalid(String),
+    /// Options were given that can't be used together, or something required
+    /// is missing, so the usage is worth showing.
+    Usage(String),
+}
+
+impl From<String> for SettingsError {
+    fn from(error: String) -> SettingsError {
+        SettingsError::Invalid(error)
+    }
+}
+
+/// Builds the settings from parsed command-line options, checking that they
+/// can be used together.
+pub fn settings(matches: &Matches, input_paths: &[&Path]) -> Result<Settings, SettingsError> {
+    let usage = |error: &str| Err(SettingsError::Usage(error.to_string()));
+
+    let named_formats = match matches.opt_str("formats") {
+        Some(path) => named_formats(Path::new(&path))?,
+        None => HashMap::new(),
+    };
+
+    let colortype = match matches.opt_str("master") {
+        Some(_) if matches.opt_present("colortype") => {
+            return usage("--master can't be used with --colortype.")
+        }
+        Some(input) => ColorType::Master(master_palette(&input)?),
+        None => color_type(matches.opt_str("colortype"), &named_formats)?,
+    };
+
+    let num_colors: u32 = number(matches, "colors", 256)?;
+    if num_colors > 256 {
+        return Err("More than 256 colors in the palette is not supported."
+            .to_string()
+            .into());
+    }
+
+    let restarts: u32 = number(matches, "restarts", 1)?;
+    if restarts == 0 {
+        return Err("The number of restarts must be at least 1."
+            .to_string()
+            .into());
+    }
+
+    let refine_moves: u32 = number(matches, "refine", 0)?;
+
+    let matte = match matches.opt_str("matte") {
+        Some(string) => Some(color(&string)?),
+        None => None,
+    };
+    let alpha_weight = match matches.opt_str("alpha-weight") {
+        Some(string) => alpha_weight(&string)?,
+        None => 3.0,
+    };
+    let outlier_threshold = match matches.opt_str("protect-outliers") {
+        Some(string) => Some(outlier_threshold(&string)?),
+        None => None,
+    };
+    let edge_weight = match matches.opt_str("edge-weight") {
+        Some(string) => Some(factor(&string)?),
+        None => None,
+    };
+    let flat_weight = match matches.opt_str("flat-weight") {
+        Some(string) => Some(factor(&string)?),
+        None => None,
+    };
+
+    let locked = matches
+        .opt_strs("lock")
+        .iter()
+        .map(|string| locked_colors(string))
+        .collect::<Result<_, _>>()?;
+
+    let remap: Vec<PathBuf> = matches
+        .opt_strs("remap")
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    if !remap.is_empty() {
+        for option in &[
+            "lock",
+            "target-error",
+            "refine",
+            "add-to",
+            "protect-outliers",
+            "mask",
+            "edge-weight",
+            "flat-weight",
+        ] {
+            if matches.opt_present(option) {
+                return usage(&format!("--remap can't be used with --{}.", option));
+            }
+        }
+    } else if matches.opt_present("dither") {
+        return usage("--dither can only be used with --remap.");
+    }
+
+    // Remapping and adding to indexes take their palettes from elsewhere, but
+    // otherwise there has to be room for a color besides a reserved index.
+    if remap.is_empty() && !matches.opt_present("add-to") {
+        let available_colors = match &colortype {
+            ColorType::Master(palette) => {
+                let combinations = (palette.len() as u32).saturating_pow(input_paths.len() as u32);
+                num_colors.min(combinations)
+            }
+            _ => num_colors,
+        };
+        if matches.opt_present("transparent-index") || colortype.reserves_transparent_index() {
+            if available_colors < 2 {
+                return Err(format!(
+                    "Reserving palette index 0 for transparency needs at least 2 colors, but there are only {}.",
+                    available_colors
+                )
+                .into());
+            }
+        } else if available_colors < 1 {
+            return Err("The palette must have at least 1 color.".to_string().into());
+        }
+    }
+
+    let raw = if matches.opt_present("raw") {
+        let bits_per_index: u8 = number(matches, "index-bits", 8)?;
+        if bits_per_index != 4 && bits_per_index != 8 {
+            return Err("Indexes must be 4 or 8 bits.".to_string().into());
+        }
+        let row_alignment: u32 = number(matches, "row-align", 1)?;
+        if row_alignment == 0 {
+            return Err("The row alignment must be at least 1 byte."
+                .to_string()
+                .into());
+        }
+        Some(RawLayout {
+            bits_per_index,
+            low_nibble_first: matches.opt_present("low-nibble-first"),
+            row_alignment,
+            header: matches.opt_present("raw-header"),
+        })
+    } else {
+        for option in &["index-bits", "low-nibble-first", "row-align", "raw-header"] {
+            if matches.opt_present(option) {
+                return usage(&format!("--{} can only be used with --raw.", option));
+            }
+        }
+        None
+    };
+
+    if matches.opt_present("bundle") && raw.is_some() {
+        return usage("--bundle can't be used with --raw.");
+    }
+    if matches.opt_present("add-to") && matches.opt_present("compose") {
+        return usage("--add-to can't be used with --compose.");
+    }
+
+    let source_languages = matches
+        .opt_strs("export-source")
+        .iter()
+        .map(|string| source_language(string))
+        .collect::<Result<_, _>>()?;
+
+    let values_per_line: usize = number(matches, "values-per-line", 16)?;
+    if values_per_line == 0 {
+        return Err("There must be at least 1 value per line."
+            .to_string()
+            .into());
+    }
+
+    let palette_formats = matches
+        .opt_strs("export-palette")
+        .iter()
+        .map(|string| palette_format(string))
+        .collect::<Result<_, _>>()?;
+
+    let combined_palette = match matches.opt_str("combined-palette") {
+        Some(string) => {
+            let path = PathBuf::from(string);
+            let extension = path.extension().map_or_else(String::new, |extension| {
+                extension.to_string_lossy().into_owned()
+            });
+            let format = palette_format(&extension)?;
+            Some((path, format))
+        }
+        None => None,
+    };
+
+    let weights = match matches.opt_str("weights") {
+        Some(input) => image_weights(&input, input_paths)?,
+        None => Vec::new(),
+    };
+
+    Ok(Settings {
+        colortype,
+        num_colors,
+        restarts,
+        refine_moves,
+        error_target: error_target(matches.opt_str("target-error"))?,
+        outlier_threshold,
+        alpha: alpha_mode(matches.opt_str("alpha"), matte)?,
+        alpha_weight,
+        weights,
+        masks: matches.opt_strs("mask").iter().map(PathBuf::from).collect(),
+        edge_weight,
+        flat_weight,
+        locked,
+        luma: luma(matches.opt_str("luma"))?,
+        // Adding to existing indexes always reports the error, since it can't
+        // be lowered by asking for more colors.
+        report: matches.opt_present("report") || matches.opt_present("add-to"),
+        report_json: matches.opt_str("report-json").map(PathBuf::from),
+        heatmap: matches.opt_present("heatmap"),
+        composite: matches.opt_present("composite"),
+        raw_palette: matches.opt_present("raw-palette"),
+        remap,
+        dither: matches.opt_present("dither"),
+        transparent_index: matches.opt_present("transparent-index"),
+        raw,
+        bundle: matches.opt_present("bundle"),
+        source_languages,
+        symbol_prefix: symbol_prefix(&matches.opt_str("symbol-prefix").unwrap_or_default())?,
+        values_per_line,
+        palette_formats,
+        combined_palette,
+        verbose: matches.opt_present("verbose"),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ColorType {
     Rgba8,
     Rgb5a3,
+    Rgb565,
+    Ia8,
+    Ia4,
+    Bgr555,
+    Abgr1555,
+    Rgba4444,
+    Argb1555,
+    /// A format described by its bit layout.
+    Custom(Format),
+    /// The colors of a console's fixed master palette.
+    Master(MasterPalette),
 }
 
-pub fn color_type(input: Option<String>) -> Result<ColorType, String> {
+impl ColorType {
+    pub fn name(&self) -> &str {
+        match self {
+            ColorType::Rgba8 => "RGBA8",
+            ColorType::Rgb5a3 => "RGB5A3",
+            ColorType::Rgb565 => "RGB565",
+            ColorType::Ia8 => "IA8",
+            ColorType::Ia4 => "IA4",
+            ColorType::Bgr555 => "BGR555",
+            ColorType::Abgr1555 => "ABGR1555",
+            ColorType::Rgba4444 => "RGBA4444",
+            ColorType::Argb1555 => "ARGB1555",
+            ColorType::Custom(format) => format.layout(),
+            ColorType::Master(palette) => palette.name(),
+        }
+    }
+
+    /// Whether the color type stores intensity rather than color.
+    pub fn is_grayscale(&self) -> bool {
+        match self {
+            ColorType::Ia8 | ColorType::Ia4 => true,
+            ColorType::Custom(format) => format.is_grayscale(),
+            _ => false,
+        }
+    }
+
+    /// Whether the color type can store transparent colors.
+    pub fn has_alpha(&self) -> bool {
+        match self {
+            ColorType::Rgba8
+            | ColorType::Rgb5a3
+            | ColorType::Ia8
+            | ColorType::Ia4
+            | ColorType::Abgr1555
+            | ColorType::Rgba4444
+            | ColorType::Argb1555 => true,
+            ColorType::Rgb565 | ColorType::Bgr555 | ColorType::Master(_) => false,
+            ColorType::Custom(format) => format.has_alpha(),
+        }
+    }
+
+    /// Whether palette index 0 is kept for fully transparent pixels, as the
+    /// consoles using the color type draw it.
+    pub fn reserves_transparent_index(&self) -> bool {
+        matches!(self, ColorType::Bgr555 | ColorType::Abgr1555)
+    }
+}
+
+/// Parses a color type, which is either a built-in type, a format named in
+/// `named_formats`, or a bit layout such as `R5G6B5`.
+pub fn color_type(
+    input: Option<String>,
+    named_formats: &HashMap<String, String>,
+) -> Result<ColorType, String> {
     match input {
         Some(string) => {
             let colortype = string.to_uppercase();
             match colortype.deref() {
                 "RGBA8" => Ok(ColorType::Rgba8),
                 "RGB5A3" => Ok(ColorType::Rgb5a3),
-                _ => Err(format!("Unknown color type {}", string)),
+                "RGB565" => Ok(ColorType::Rgb565),
+                "IA8" => Ok(ColorType::Ia8),
+                "IA4" => Ok(ColorType::Ia4),
+                "BGR555" => Ok(ColorType::Bgr555),
+                "ABGR1555" => Ok(ColorType::Abgr1555),
+                "RGBA4444" => Ok(ColorType::Rgba4444),
+                "ARGB1555" => Ok(ColorType::Argb1555),
+                _ => {
+                    let layout = match named_formats.get(&colortype) {
+                        Some(layout) => layout,
+                        None if string.chars().any(|c| c.is_ascii_digit()) => &string,
+                        None => return Err(format!("Unknown color type {}", string)),
+                    };
+                    let format = Format::parse(layout)?;
+                    Ok(ColorType::Custom(format))
+                }
             }
         }
         None => Ok(ColorType::Rgba8),
     }
 }
+
+/// Parses a color combination to lock into the palette, as comma-separated
+/// hex colors, one for each image or one for all of them.
+pub fn locked_colors(input: &str) -> Result<Vec<Pixel>, String> {
+    input
+        .split(',')
+        .map(|color| self::color(color.trim()))
+        .collect()
+}
+
+/// Finds a master palette, either a preset (NES, PICO-8 or Game Boy) or a
+/// file with a hex color on each line.
+pub fn master_palette(input: &str) -> Result<MasterPalette, String> {
+    let palette = match palette::preset(input) {
+        Some(palette) => palette,
+        None => {
+            let path = Path::new(input);
+            let colors = palette::read_hex(path)
+                .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+            let name = path.file_stem().map_or_else(
+                || input.to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+            MasterPalette::new(&name, colors)
+        }
+    };
+    Ok(palette)
+}
+
+/// Parses image weights, either comma-separated numbers with one for each
+/// image, or a file with a `PATH = WEIGHT` on each line. Images missing from
+/// the file have a weight of 1.
+pub fn image_weights(input: &str, input_paths: &[&Path]) -> Result<Vec<f64>, String> {
+    let weights: Result<Vec<f64>, _> = input
+        .split(',')
+        .map(|weight| weight.trim().parse())
+        .collect();
+    let weights = match weights {
+        Ok(weights) => weights,
+        Err(_) => weights_from_file(Path::new(input), input_paths)?,
+    };
+    if let Some(weight) = weights
+        .iter()
+        .find(|weight| !(weight.is_finite() && **weight > 0.0))
+    {
+        return Err(format!(
+            "Invalid weight {}; weights must be positive",
+            weight
+        ));
+    }
+    Ok(weights)
+}
+
+fn weights_from_file(path: &Path, input_paths: &[&Path]) -> Result<Vec<f64>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+
+    let mut weights = vec![1.0; input_paths.len()];
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || {
+            format!(
+                "{}:{}: expected PATH = WEIGHT",
+                path.display(),
+                line_number + 1
+            )
+        };
+        let mut parts = line.rsplitn(2, '=');
+        let weight: f64 = parts
+            .next()
+            .and_then(|weight| weight.trim().parse().ok())
+            .ok_or_else(error)?;
+        let image_path = Path::new(parts.next().ok_or_else(error)?.trim());
+        match input_paths
+            .iter()
+            .position(|&input_path| input_path == image_path)
+        {
+            Some(index) => weights[index] = weight,
+            None => {
+                return Err(format!(
+                    "{}:{}: {} isn't one of the input images",
+                    path.display(),
+                    line_number + 1,
+                    image_path.display()
+                ))
+            }
+        }
+    }
+    Ok(weights)
+}
+
+/// The largest factor pixel weights can be scaled by. Edge and flat weights
+/// both at this, times a full mask, still fit a pixel's weight in a u32.
+pub const MAX_FACTOR: f64 = 1000.0;
+
+/// Parses a positive number, at most `MAX_FACTOR`, to scale how much pixels
+/// count.
+pub fn factor(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor <= MAX_FACTOR => Ok(factor),
+        _ => Err(format!(
+            "Invalid factor {}; it must be positive and at most {}",
+            input, MAX_FACTOR
+        )),
+    }
+}
+
+/// Parses how much a difference in alpha counts. Zero is allowed, to compare
+/// colors by their color alone.
+pub fn alpha_weight(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok(weight),
+        _ => Err(format!(
+            "Invalid alpha weight {}; it can't be negative",
+            input
+        )),
+    }
+}
+
+/// Parses the distance past which a color combination is an outlier.
+pub fn outlier_threshold(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(threshold) if threshold.is_finite() && threshold > 0.0 => Ok(threshold),
+        _ => Err(format!(
+            "Invalid outlier distance {}; it must be positive",
+            input
+        )),
+    }
+}
+
+/// Reads named color formats from a file, with a `NAME = LAYOUT` on each
+/// line. Blank lines and lines starting with # are ignored, and names are
+/// case-insensitive like the built-in color types.
+pub fn named_formats(path: &Path) -> Result<HashMap<String, String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+
+    let mut formats = HashMap::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(layout)) if !name.trim().is_empty() => {
+                formats.insert(name.trim().to_uppercase(), layout.trim().to_string());
+            }
+            _ => {
+                return Err(format!(
+                    "{}:{}: expected NAME = LAYOUT",
+                    path.display(),
+                    line_number + 1
+                ))
+            }
+        }
+    }
+    Ok(formats)
+}
+
+/// How the indexes are laid out in raw output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RawLayout {
+    /// 8, or 4 to pack two indexes into each byte.
+    pub bits_per_index: u8,
+    /// With 4 bits per index, put the first of each pair of pixels in the low
+    /// nibble instead of the high one.
+    pub low_nibble_first: bool,
+    /// Pad each row with zeros to a multiple of this many bytes.
+    pub row_alignment: u32,
+    /// Start each file with a small header describing it.
+    pub header: bool,
+}
+
+impl Default for RawLayout {
+    fn default() -> RawLayout {
+        RawLayout {
+            bits_per_index: 8,
+            low_nibble_first: false,
+            row_alignment: 1,
+            header: false,
+        }
+    }
+}
+
+/// A programming language to write the indexes and palettes in, to build
+/// them into a program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceLanguage {
+    /// A C header.
+    C,
+    /// A Rust module.
+    Rust,
+}
+
+impl SourceLanguage {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SourceLanguage::C => "h",
+            SourceLanguage::Rust => "rs",
+        }
+    }
+}
+
+pub fn source_language(input: &str) -> Result<SourceLanguage, String> {
+    match input.to_lowercase().as_str() {
+        "c" | "h" => Ok(SourceLanguage::C),
+        "rust" | "rs" => Ok(SourceLanguage::Rust),
+        _ => Err(format!("Unknown source language {}", input)),
+    }
+}
+
+/// Checks that a symbol prefix can start an identifier in C and Rust.
+pub fn symbol_prefix(input: &str) -> Result<String, String> {
+    let valid = input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !input.starts_with(|c: char| c.is_ascii_digit());
+    if valid {
+        Ok(input.to_string())
+    } else {
+        Err(format!("Invalid symbol prefix {}", input))
+    }
+}
+
+/// A palette file format for other programs to read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaletteFormat {
+    /// Adobe Color Table, for Photoshop.
+    Act,
+    /// GIMP palette, which Aseprite and Inkscape read too.
+    Gpl,
+    /// JASC-PAL, for Paint Shop Pro.
+    Jasc,
+    /// A hex color on each line.
+    Hex,
+}
+
+impl PaletteFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteFormat::Act => "act",
+            PaletteFormat::Gpl => "gpl",
+            PaletteFormat::Jasc => "pal",
+            PaletteFormat::Hex => "hex",
+        }
+    }
+}
+
+/// Parses a palette format by name or file extension.
+pub fn palette_format(input: &str) -> Result<PaletteFormat, String> {
+    match input.to_lowercase().as_str() {
+        "act" => Ok(PaletteFormat::Act),
+        "gpl" | "gimp" => Ok(PaletteFormat::Gpl),
+        "pal" | "jasc" => Ok(PaletteFormat::Jasc),
+        "hex" | "txt" => Ok(PaletteFormat::Hex),
+        _ => Err(format!("Unknown palette format {}", input)),
+    }
+}
+
+/// How partially transparent pixels are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Keep alpha as it is, comparing colors scaled by both alphas.
+    Keep,
+    /// Make every pixel fully transparent or opaque, for 1-bit transparency.
+    Threshold,
+    /// Keep alpha as it is, comparing premultiplied colors.
+    Premultiplied,
+    /// Composite every pixel over a color, making it opaque.
+    Matte(Pixel),
+}
+
+/// Parses the alpha mode, where giving a matte color implies flattening
+/// against it.
+pub fn alpha_mode(input: Option<String>, matte: Option<Pixel>) -> Result<AlphaMode, String> {
+    let mode = match input {
+        Some(mode) => mode.to_lowercase(),
+        None => return Ok(matte.map_or(AlphaMode::Keep, AlphaMode::Matte)),
+    };
+    match (mode.as_str(), matte) {
+        ("matte", Some(matte)) => Ok(AlphaMode::Matte(matte)),
+        ("matte", None) => Err("The matte alpha mode needs a --matte color".to_string()),
+        (_, Some(_)) => Err("--matte can only be used with the matte alpha mode".to_string()),
+        ("keep", None) => Ok(AlphaMode::Keep),
+        ("threshold", None) => Ok(AlphaMode::Threshold),
+        ("premultiplied", None) => Ok(AlphaMode::Premultiplied),
+        _ => Err(format!("Unknown alpha mode {}", mode)),
+    }
+}
+
+/// The standard whose luma coefficients convert colors to intensity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Luma {
+    Rec601,
+    Rec709,
+}
+
+pub fn luma(input: Option<String>) -> Result<Luma, String> {
+    match input {
+        Some(string) => {
+            let standard = string.to_uppercase();
+            match standard.trim_start_matches("REC").trim_start_matches('.') {
+                "601" => Ok(Luma::Rec601),
+                "709" => Ok(Luma::Rec709),
+                _ => Err(format!("Unknown luma standard {}", string)),
+            }
+        }
+        None => Ok(Luma::Rec601),
+    }
+}
+
+/// The largest acceptable error, for choosing the number of colors
+/// automatically.
+///
+/// Errors are per pixel of each image, using the same distance as clustering,
+/// with color components scaled to the range 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorTarget {
+    Mean(f64),
+    Max(f64),
+    /// The minimum peak signal-to-noise ratio, in decibels.
+    Psnr(f64),
+}
+
+/// Parses an error target of the form `mean:VALUE`, `max:VALUE` or
+/// `psnr:VALUE`.
+pub fn error_target(input: Option<String>) -> Result<Option<ErrorTarget>, String> {
+    let string = match input {
+        Some(string) => string,
+        None => return Ok(None),
+    };
+
+    let mut parts = string.splitn(2, ':');
+    let kind = parts.next().unwrap().to_lowercase();
+    let value: f64 = match parts.next().map(str::parse) {
+        Some(Ok(value)) => value,
+        _ => return Err(format!("Invalid error target {}", string)),
+    };
+    if value.is_nan() || value < 0.0 {
+        return Err(format!("Invalid error target {}", string));
+    }
+
+    match kind.deref() {
+        "mean" => Ok(Some(ErrorTarget::Mean(value))),
+        "max" => Ok(Some(ErrorTarget::Max(value))),
+        "psnr" => Ok(Some(ErrorTarget::Psnr(value))),
+        _ => Err(format!("Unknown error target type {}", kind)),
+    }
+}
+
+/// Parses a color written in hex as RRGGBB or RRGGBBAA, optionally starting
+/// with #.
+pub fn color(input: &str) -> Result<Pixel, String> {
+    let hex = input.trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color {}", input));
+    }
+
+    let mut data = [0xFF; 4];
+    for (i, channel) in data.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    Ok(Pixel { data })
+}
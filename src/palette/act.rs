@@ -0,0 +1,66 @@
+//! Adobe Color Table files, as used by Photoshop.
+//!
+//! These hold 256 RGB colors, optionally followed by the number of colors
+//! actually used and the index of the transparent color, each as a
+//! big-endian 16 bit number (0xFFFF for no transparent color).
+
+use color::Pixel;
+
+const COLORS: usize = 256;
+
+pub fn parse(bytes: &[u8]) -> Result<Vec<Pixel>, String> {
+    let (count, transparent_index) = match bytes.len() {
+        768 => (COLORS, None),
+        772 => {
+            let count = usize::from(u16::from_be_bytes([bytes[768], bytes[769]]));
+            let transparent_index = u16::from_be_bytes([bytes[770], bytes[771]]);
+            let transparent_index = if transparent_index == 0xFFFF {
+                None
+            } else {
+                Some(usize::from(transparent_index))
+            };
+            (count.min(COLORS), transparent_index)
+        }
+        length => {
+            return Err(format!(
+                "{} bytes is the wrong size for an ACT file",
+                length
+            ))
+        }
+    };
+
+    Ok(bytes[..count * 3]
+        .chunks(3)
+        .enumerate()
+        .map(|(index, rgb)| {
+            if Some(index) == transparent_index {
+                Pixel { data: [0, 0, 0, 0] }
+            } else {
+                Pixel {
+                    data: [rgb[0], rgb[1], rgb[2], 0xFF],
+                }
+            }
+        })
+        .collect())
+}
+
+pub fn write(colors: &[Pixel]) -> Result<Vec<u8>, String> {
+    if colors.len() > COLORS {
+        return Err(format!(
+            "{} colors is more than an ACT file can hold",
+            colors.len()
+        ));
+    }
+
+    let mut bytes = vec![0; COLORS * 3];
+    for (rgb, color) in bytes.chunks_mut(3).zip(colors) {
+        rgb.copy_from_slice(&color.data[..3]);
+    }
+    let transparent_index = colors
+        .iter()
+        .position(|color| color[3] == 0)
+        .map_or(0xFFFF, |index| index as u16);
+    bytes.extend_from_slice(&(colors.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&transparent_index.to_be_bytes());
+    Ok(bytes)
+}
@@ -0,0 +1,37 @@
+//! GIMP palettes, which Aseprite and Inkscape use too.
+//!
+//! After a "GIMP Palette" line come optional Name and Columns lines, then a
+//! color on each line as decimal red, green and blue, optionally followed by
+//! a name. Lines starting with # are comments.
+
+use color::Pixel;
+
+use super::parse_components;
+
+pub fn parse(text: &str) -> Result<Vec<Pixel>, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("GIMP Palette") {
+        return Err("not a GIMP palette".to_string());
+    }
+
+    lines
+        .map(str::trim)
+        .filter(|line| {
+            !(line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:"))
+        })
+        .map(parse_components)
+        .collect()
+}
+
+/// Writes a GIMP palette, naming each color by its hex value.
+pub fn write(name: &str, colors: &[Pixel]) -> String {
+    let mut text = format!("GIMP Palette\nName: {}\nColumns: 16\n#\n", name);
+    for color in colors {
+        let [r, g, b, _] = color.data;
+        text += &format!("{:3} {:3} {:3}\t#{:02X}{:02X}{:02X}\n", r, g, b, r, g, b);
+    }
+    text
+}
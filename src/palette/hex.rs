@@ -0,0 +1,34 @@
+//! Plain lists of hex colors, as used by Paint.NET and Lospec.
+
+use color::Pixel;
+use options;
+
+/// Parses a palette with one color per line, written in hex as RRGGBB or
+/// RRGGBBAA.
+///
+/// Lines may start with #, and blank lines and lines starting with ; are
+/// ignored.
+pub fn parse(text: &str) -> Result<Vec<Pixel>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(line_number, line)| (line_number, line.trim()))
+        .filter(|&(_, line)| !line.is_empty() && !line.starts_with(';'))
+        .map(|(line_number, line)| {
+            options::color(line).map_err(|error| format!("line {}: {}", line_number + 1, error))
+        })
+        .collect()
+}
+
+/// Writes a color on each line as RRGGBB, or RRGGBBAA if it isn't opaque.
+pub fn write(colors: &[Pixel]) -> String {
+    let mut text = String::new();
+    for color in colors {
+        let [r, g, b, a] = color.data;
+        text += &format!("{:02X}{:02X}{:02X}", r, g, b);
+        if a != 0xFF {
+            text += &format!("{:02X}", a);
+        }
+        text.push('\n');
+    }
+    text
+}
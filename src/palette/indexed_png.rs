@@ -0,0 +1,34 @@
+//! The palettes of indexed PNG images, such as earlier output.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use png;
+
+use color::Pixel;
+
+pub fn read(path: &Path) -> io::Result<Vec<Pixel>> {
+    let (_output_info, reader) = png::Decoder::new(File::open(path)?).read_info()?;
+    let info = reader.info();
+
+    let rgb_palette = match info.palette {
+        Some(ref palette) if info.color_type == png::ColorType::Indexed => palette,
+        _ => {
+            let message = format!("{}: not an indexed image", path.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+    };
+    let alpha_palette = info.trns.as_ref().map_or(&[][..], |trns| &trns[..]);
+
+    Ok(rgb_palette
+        .chunks(3)
+        .enumerate()
+        .map(|(index, rgb)| {
+            let alpha = alpha_palette.get(index).cloned().unwrap_or(0xFF);
+            Pixel {
+                data: [rgb[0], rgb[1], rgb[2], alpha],
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,41 @@
+//! JASC palettes, from Paint Shop Pro.
+//!
+//! These start with "JASC-PAL", the version "0100" and the number of colors,
+//! then have a color on each line as decimal red, green and blue.
+
+use color::Pixel;
+
+use super::parse_components;
+
+pub fn parse(text: &str) -> Result<Vec<Pixel>, String> {
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some("JASC-PAL") {
+        return Err("not a JASC palette".to_string());
+    }
+    if lines.next() != Some("0100") {
+        return Err("unsupported JASC palette version".to_string());
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|line| line.parse().ok())
+        .ok_or_else(|| "missing color count".to_string())?;
+
+    let colors = lines
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .map(parse_components)
+        .collect::<Result<Vec<_>, _>>()?;
+    if colors.len() < count {
+        return Err(format!("expected {} colors, found {}", count, colors.len()));
+    }
+    Ok(colors)
+}
+
+/// Writes a JASC palette, with the Windows line endings Paint Shop Pro uses.
+pub fn write(colors: &[Pixel]) -> String {
+    let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+    for color in colors {
+        text += &format!("{} {} {}\r\n", color[0], color[1], color[2]);
+    }
+    text
+}
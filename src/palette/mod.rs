@@ -0,0 +1,131 @@
+//! Reads and writes palettes as files, and holds the master palettes of
+//! consoles.
+//!
+//! Palettes are lists of colors in palette order; transparency is kept in
+//! the alpha of each color for the formats that have it.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use color::{Color, Pixel};
+use options::PaletteFormat;
+
+mod act;
+mod gpl;
+mod hex;
+mod indexed_png;
+mod jasc;
+mod presets;
+pub use self::presets::preset;
+
+#[cfg(test)]
+mod tests;
+
+/// Reads a palette, with the format chosen by the file extension: an indexed
+/// PNG, Adobe ACT, GIMP GPL, JASC-PAL (.pal), or a list of hex colors
+/// (.hex or .txt).
+pub fn read(path: &Path) -> io::Result<Vec<Pixel>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let colors = match extension.as_str() {
+        "png" => Ok(indexed_png::read(path)?),
+        "act" => act::parse(&fs::read(path)?),
+        "gpl" => gpl::parse(&fs::read_to_string(path)?),
+        "pal" => jasc::parse(&fs::read_to_string(path)?),
+        "hex" | "txt" => hex::parse(&fs::read_to_string(path)?),
+        _ => Err(format!("unknown palette format .{}", extension)),
+    };
+    checked(path, colors)
+}
+
+/// Writes a palette in a format for other programs to read. Colors in formats
+/// without alpha are written as if opaque, except that ACT files mark the
+/// first fully transparent color.
+pub fn write(path: &Path, format: PaletteFormat, colors: &[Pixel]) -> io::Result<()> {
+    let name = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let contents = match format {
+        PaletteFormat::Act => act::write(colors),
+        PaletteFormat::Gpl => Ok(gpl::write(&name, colors).into_bytes()),
+        PaletteFormat::Jasc => Ok(jasc::write(colors).into_bytes()),
+        PaletteFormat::Hex => Ok(hex::write(colors).into_bytes()),
+    };
+    match contents {
+        Ok(contents) => fs::write(path, contents),
+        Err(message) => {
+            let message = format!("{}: {}", path.display(), message);
+            Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+        }
+    }
+}
+
+/// Reads a palette with one color per line, written in hex as RRGGBB.
+pub fn read_hex(path: &Path) -> io::Result<Vec<Pixel>> {
+    checked(path, hex::parse(&fs::read_to_string(path)?))
+}
+
+/// Reads a palette of colors in their native format, as written by
+/// `--raw-palette` or `--raw`, with or without a header.
+pub fn read_native<C: Color>(path: &Path, context: C::Context) -> io::Result<Vec<Pixel>> {
+    let bytes = fs::read(path)?;
+    let size = C::native_size(context);
+    let bytes = without_raw_header(&bytes, size);
+    let colors = if bytes.len() % size != 0 {
+        Err(format!(
+            "{} bytes isn't a whole number of {} byte colors",
+            bytes.len(),
+            size
+        ))
+    } else {
+        bytes
+            .chunks(size)
+            .enumerate()
+            .map(|(index, native)| {
+                C::from_native(native, context)
+                    .map(|color| color.as_pixel())
+                    .ok_or_else(|| format!("color {} isn't valid", index))
+            })
+            .collect()
+    };
+    checked(path, colors)
+}
+
+/// Skips the header `--raw-header` writes, if the palette has one.
+fn without_raw_header(bytes: &[u8], size: usize) -> &[u8] {
+    if bytes.len() >= 8 && &bytes[..4] == b"CSPL" {
+        let num_colors = usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+        if usize::from(bytes[6]) == size && bytes.len() == 8 + num_colors * size {
+            return &bytes[8..];
+        }
+    }
+    bytes
+}
+
+/// Adds the path to parsing errors, and rejects empty palettes.
+fn checked(path: &Path, colors: Result<Vec<Pixel>, String>) -> io::Result<Vec<Pixel>> {
+    let message = match colors {
+        Ok(ref colors) if colors.is_empty() => "no colors".to_string(),
+        Ok(colors) => return Ok(colors),
+        Err(message) => message,
+    };
+    let message = format!("{}: {}", path.display(), message);
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Parses a line of decimal color components, as in GPL and JASC-PAL files,
+/// ignoring anything after them.
+fn parse_components(line: &str) -> Result<Pixel, String> {
+    let mut data = [0xFF; 4];
+    let mut components = line.split_whitespace();
+    for channel in data.iter_mut().take(3) {
+        *channel = components
+            .next()
+            .and_then(|component| component.parse().ok())
+            .ok_or_else(|| format!("invalid color {}", line))?;
+    }
+    Ok(Pixel { data })
+}
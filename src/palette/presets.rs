@@ -0,0 +1,106 @@
+//! The master palettes of consoles with a fixed set of colors.
+
+use color::{MasterPalette, Pixel};
+
+/// The NES's colors as (code, RRGGBB).
+///
+/// The hardware has 64 codes, but 0x0D is blacker than black and upsets some
+/// TVs, the 0xE and 0xF columns are more black, 0x1D is black too, and 0x20 is
+/// the same white as 0x30, which leaves 54 distinct colors.
+const NES: [(u32, u32); 54] = [
+    (0x00, 0x7C7C7C),
+    (0x01, 0x0000FC),
+    (0x02, 0x0000BC),
+    (0x03, 0x4428BC),
+    (0x04, 0x940084),
+    (0x05, 0xA80020),
+    (0x06, 0xA81000),
+    (0x07, 0x881400),
+    (0x08, 0x503000),
+    (0x09, 0x007800),
+    (0x0A, 0x006800),
+    (0x0B, 0x005800),
+    (0x0C, 0x004058),
+    (0x0F, 0x000000),
+    (0x10, 0xBCBCBC),
+    (0x11, 0x0078F8),
+    (0x12, 0x0058F8),
+    (0x13, 0x6844FC),
+    (0x14, 0xD800CC),
+    (0x15, 0xE40058),
+    (0x16, 0xF83800),
+    (0x17, 0xE45C10),
+    (0x18, 0xAC7C00),
+    (0x19, 0x00B800),
+    (0x1A, 0x00A800),
+    (0x1B, 0x00A844),
+    (0x1C, 0x008888),
+    (0x21, 0x3CBCFC),
+    (0x22, 0x6888FC),
+    (0x23, 0x9878F8),
+    (0x24, 0xF878F8),
+    (0x25, 0xF85898),
+    (0x26, 0xF87858),
+    (0x27, 0xFCA044),
+    (0x28, 0xF8B800),
+    (0x29, 0xB8F818),
+    (0x2A, 0x58D854),
+    (0x2B, 0x58F898),
+    (0x2C, 0x00E8D8),
+    (0x2D, 0x787878),
+    (0x30, 0xFCFCFC),
+    (0x31, 0xA4E4FC),
+    (0x32, 0xB8B8F8),
+    (0x33, 0xD8B8F8),
+    (0x34, 0xF8B8F8),
+    (0x35, 0xF8A4C0),
+    (0x36, 0xF0D0B0),
+    (0x37, 0xFCE0A8),
+    (0x38, 0xF8D878),
+    (0x39, 0xD8F878),
+    (0x3A, 0xB8F8B8),
+    (0x3B, 0xB8F8D8),
+    (0x3C, 0x00FCFC),
+    (0x3D, 0xF8D8F8),
+];
+
+const PICO8: [u32; 16] = [
+    0x000000, 0x1D2B53, 0x7E2553, 0x008751, 0xAB5236, 0x5F574F, 0xC2C3C7, 0xFFF1E8, 0xFF004D,
+    0xFFA300, 0xFFEC27, 0x00E436, 0x29ADFF, 0x83769C, 0xFF77A8, 0xFFCCAA,
+];
+
+/// The original Game Boy's four shades of green, from shade 0 (the lightest)
+/// to 3.
+const GAMEBOY: [u32; 4] = [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F];
+
+/// The master palette with the given name (NES, PICO-8 or Game Boy), ignoring
+/// case and punctuation.
+pub fn preset(name: &str) -> Option<MasterPalette> {
+    let simplified: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    match simplified.as_str() {
+        "nes" => Some(MasterPalette::with_codes(
+            "NES",
+            NES.iter().map(|&(_code, rgb)| pixel(rgb)).collect(),
+            NES.iter().map(|&(code, _rgb)| code).collect(),
+        )),
+        "pico8" => Some(MasterPalette::new(
+            "PICO-8",
+            PICO8.iter().map(|&rgb| pixel(rgb)).collect(),
+        )),
+        "gameboy" | "gb" => Some(MasterPalette::new(
+            "Game Boy",
+            GAMEBOY.iter().map(|&rgb| pixel(rgb)).collect(),
+        )),
+        _ => None,
+    }
+}
+
+fn pixel(rgb: u32) -> Pixel {
+    Pixel {
+        data: [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF],
+    }
+}
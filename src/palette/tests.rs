@@ -0,0 +1,111 @@
+use color::Pixel;
+
+use super::{act, gpl, hex, jasc, without_raw_header};
+
+fn opaque(r: u8, g: u8, b: u8) -> Pixel {
+    Pixel {
+        data: [r, g, b, 0xFF],
+    }
+}
+
+#[test]
+fn reads_act_with_count_and_transparent_index() {
+    let mut bytes = vec![0; 772];
+    bytes[..6].copy_from_slice(&[0x10, 0x20, 0x30, 0xFF, 0x00, 0x80]);
+    bytes[768..].copy_from_slice(&[0x00, 0x02, 0x00, 0x01]);
+    let expected = vec![opaque(0x10, 0x20, 0x30), Pixel { data: [0, 0, 0, 0] }];
+    assert_eq!(Ok(expected), act::parse(&bytes));
+}
+
+#[test]
+fn reads_act_without_count_as_256_colors() {
+    let colors = act::parse(&[0x40; 768]).unwrap();
+    assert_eq!(256, colors.len());
+    assert!(colors
+        .iter()
+        .all(|&color| color == opaque(0x40, 0x40, 0x40)));
+}
+
+#[test]
+fn rejects_act_of_wrong_size() {
+    assert!(act::parse(&[0; 100]).is_err());
+}
+
+#[test]
+fn reads_gpl() {
+    let text = "GIMP Palette\nName: Test\nColumns: 4\n# comment\n  0   0   0\tBlack\n\
+                255 128 7 Orange\n";
+    let expected = vec![opaque(0, 0, 0), opaque(255, 128, 7)];
+    assert_eq!(Ok(expected), gpl::parse(text));
+}
+
+#[test]
+fn reads_jasc() {
+    let text = "JASC-PAL\r\n0100\r\n2\r\n255 255 255\r\n16 32 48\r\n";
+    let expected = vec![opaque(255, 255, 255), opaque(16, 32, 48)];
+    assert_eq!(Ok(expected), jasc::parse(text));
+}
+
+#[test]
+fn rejects_jasc_with_missing_colors() {
+    assert!(jasc::parse("JASC-PAL\n0100\n3\n0 0 0\n").is_err());
+}
+
+#[test]
+fn reads_hex() {
+    let text = "; paint.net palette\nFF102030\n\n#405060\n";
+    let expected = vec![
+        Pixel {
+            data: [0xFF, 0x10, 0x20, 0x30],
+        },
+        opaque(0x40, 0x50, 0x60),
+    ];
+    assert_eq!(Ok(expected), hex::parse(text));
+}
+
+fn test_palette() -> Vec<Pixel> {
+    vec![
+        Pixel { data: [0, 0, 0, 0] },
+        opaque(0xFF, 0x80, 0x07),
+        opaque(0x10, 0x20, 0x30),
+    ]
+}
+
+#[test]
+fn act_round_trips_with_transparent_index() {
+    let bytes = act::write(&test_palette()).unwrap();
+    assert_eq!(772, bytes.len());
+    assert_eq!(Ok(test_palette()), act::parse(&bytes));
+}
+
+#[test]
+fn act_rejects_more_than_256_colors() {
+    assert!(act::write(&vec![opaque(0, 0, 0); 257]).is_err());
+}
+
+#[test]
+fn gpl_round_trips_opaque_colors() {
+    let colors = test_palette()[1..].to_vec();
+    assert_eq!(Ok(colors.clone()), gpl::parse(&gpl::write("test", &colors)));
+}
+
+#[test]
+fn jasc_round_trips_opaque_colors() {
+    let colors = test_palette()[1..].to_vec();
+    assert_eq!(Ok(colors.clone()), jasc::parse(&jasc::write(&colors)));
+}
+
+#[test]
+fn hex_round_trips_with_alpha() {
+    let text = hex::write(&test_palette());
+    assert_eq!("00000000\nFF8007\n102030\n", text);
+    assert_eq!(Ok(test_palette()), hex::parse(&text));
+}
+
+#[test]
+fn skips_raw_palette_header() {
+    let with_header = [b'C', b'S', b'P', b'L', 2, 0, 2, 0, 0x12, 0x34, 0x56, 0x78];
+    assert_eq!(&with_header[8..], without_raw_header(&with_header, 2));
+    // Colors that happen to start with the magic aren't mistaken for it.
+    assert_eq!(&with_header[..8], without_raw_header(&with_header[..8], 2));
+}